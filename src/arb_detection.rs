@@ -1,86 +1,56 @@
 use super::arb_execution::execute_arbitrage_cycle;
-use super::constants::{ASSET_HOLDINGS, USD_BUDGET, MIN_ARB_THRESH, MAX_CYCLE_LENGTH, MODE};
-use super::bellmanford::Edge;
+use super::constants::{ASSET_HOLDINGS, CYCLE_COOLDOWN_MS, GRAPH_EXPORT_DIR, GRAPH_MODE, IS_PAPER_TRADE, PAPER_BALANCES, USD_BUDGET, MIN_ARB_THRESH, MODE};
+use super::bellmanford::{BellmanFord, CycleKey, Edge};
 use super::exchanges::binance::Binance;
-use super::models::{ArbData, Direction, Mode, SmartError};
+use super::exchanges::paper::{paper_balance_sheet, PaperExchange};
+use super::graph_export::GraphSnapshot;
+use super::helpers::calculate_weighted_average_price;
+use super::models::{ArbData, BinanceConfig, Direction, FeeSchedule, GraphMode, Mode, SmartError};
 use super::traits::{ApiCalls, BellmanFordEx, ExchangeData};
 
 use csv::WriterBuilder;
 use futures::future::join_all;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::collections::HashSet;
-
-/// Calculate Weighted Average Price
-/// Calculates the depth of the orderbook to get a real rate
-fn calculate_weighted_average_price(
-    orderbook: &Vec<(f64, f64)>,
-    budget: f64,
-    direction: &Direction,
-) -> Option<(f64, f64, f64)> {
-    let mut total_cost = 0.0;
-    let mut total_quantity = 0.0;
-
-    for &(price, quantity) in orderbook.iter() {
-
-        // Effective quantity is the amount of the quote asset you receive (or spend in reverse)
-        let effective_quantity = match direction {
-            Direction::Reverse => quantity,
-            Direction::Forward => quantity * price,
-        };
-
-        // Cost is the amount of the base asset you spend (or receive in forward)
-        let cost = match direction {
-            Direction::Reverse => quantity * price,
-            Direction::Forward => quantity,
-        };
-
-        // Check if adding this order exceeds the budget
-        if total_cost + cost > budget {
-            let remaining_budget = budget - total_cost;
-
-            // Adjust the remaining quantity based on the direction of the trade
-            let remaining_quantity = match direction {
-                Direction::Reverse => remaining_budget / price,
-                Direction::Forward => remaining_budget * price, // In forward, get quote asset amount
-            };
-
-            total_cost += remaining_budget;
-            total_quantity += remaining_quantity;
-            break;
-
-        } else {
-            total_cost += cost;
-            total_quantity += effective_quantity;
-        }
+use std::collections::HashMap;
+
+/// Cycle Cooldown
+/// Remembers when each canonical cycle was last acted on so one opportunity is not traded or stored repeatedly
+#[derive(Debug)]
+pub struct CycleCooldown {
+    pub cooldown: Duration,
+    pub last_seen: HashMap<CycleKey, Instant>
+}
 
-        if total_cost >= budget {
-            break;
-        }
+impl CycleCooldown {
+    pub fn new(cooldown: Duration) -> Self {
+        Self { cooldown, last_seen: HashMap::new() }
     }
 
-    // Guard: Ensure quantity is not zero
-    if total_quantity == 0.0 {
-        return None;
+    /// Is Cooling Down
+    /// True if the cycle was marked within the cooldown window
+    pub fn is_cooling_down(&self, key: &CycleKey) -> bool {
+        self.last_seen.get(key).is_some_and(|seen| seen.elapsed() < self.cooldown)
     }
 
-    // Weighted average price calculation
-    let weighted_average_price = match direction {
-        Direction::Reverse => total_cost / total_quantity,
-        Direction::Forward => total_quantity / total_cost,
-    };
-  
-    Some((weighted_average_price, total_cost, total_quantity))
+    /// Mark
+    /// Starts the cooldown window for a cycle and drops expired entries
+    pub fn mark(&mut self, key: CycleKey) {
+        let cooldown = self.cooldown;
+        self.last_seen.retain(|_, seen| seen.elapsed() < cooldown);
+        self.last_seen.insert(key, Instant::now());
+    }
 }
 
 /// Calculate Arbitrage
 /// Calculates arbitrage given relevant inputs and orderbooks
+/// Taker fees are deducted on every leg so the rate returned is net
 fn calculate_arbitrage(
-    orderbooks: &Vec<Vec<(f64, f64)>>,
-    symbols: &Vec<String>,
-    directions: &Vec<Direction>,
-    budget: f64
+    orderbooks: &[Vec<(f64, f64)>],
+    cycle: &[Edge],
+    budget: f64,
+    fees: &FeeSchedule
 ) -> Option<f64> {
 
     // Initialize
@@ -88,12 +58,11 @@ fn calculate_arbitrage(
     let mut amount_in = budget;
 
     // Perform arbitrage calculation
-    for i in 0..symbols.len() {
-        let direction = &directions[i];
-        let orderbook = &orderbooks[i];
+    for (leg, orderbook) in cycle.iter().zip(orderbooks.iter()) {
+        let direction = &leg.direction;
 
         // Calculate Average Price and quantity out
-        let trade_res: Option<(f64, f64, f64)> = calculate_weighted_average_price(orderbook, amount_in, &direction);
+        let trade_res: Option<(f64, f64, f64)> = calculate_weighted_average_price(orderbook, amount_in, direction);
 
         // Extract values
         let (weighted_price, trade_qty) = match trade_res {
@@ -101,13 +70,14 @@ fn calculate_arbitrage(
             None => return None
         };
 
-        // Update amount in for next leg budget amount
-        amount_in = trade_qty;
+        // Update amount in for next leg budget amount (fee is taken from the asset received)
+        let fee_retained = 1.0 - fees.taker_fee(&leg.symbol);
+        amount_in = trade_qty * fee_retained;
 
         // Calculate Real Rate
         match direction {
-            Direction::Forward => real_rate *= weighted_price,
-            Direction::Reverse => real_rate *= 1.0 / weighted_price,
+            Direction::Forward => real_rate *= weighted_price * fee_retained,
+            Direction::Reverse => real_rate *= fee_retained / weighted_price,
         }
     }
 
@@ -118,13 +88,14 @@ fn calculate_arbitrage(
 
 /// Validate Arbitrage Cycle
 /// Validates arbitrage cycle has enough depth
-pub async fn validate_arbitrage_cycle<T: BellmanFordEx>(cycle: &Vec<Edge>, exchange: &T) 
-    -> Option<(f64, Vec<String>, Vec<Direction>, f64)> 
+/// Returns the real rate and the starting budget
+pub async fn validate_arbitrage_cycle<T>(cycle: &[Edge], exchange: &T) 
+    -> Option<(f64, f64)> 
 where T: BellmanFordEx + ExchangeData + ApiCalls 
 {
 
     // Guard: Ensure cycle
-    if cycle.len() == 0 { return None };
+    if cycle.is_empty() { return None };
 
     // Guard: Ensure asset holding
     let from = cycle[0].from.as_str();
@@ -149,28 +120,11 @@ where T: BellmanFordEx + ExchangeData + ApiCalls
     };
 
     // Initialize
-    let mut symbols: Vec<String> = vec![];
-    let mut directions: Vec<Direction> = vec![];
     let mut orderbooks: Vec<Vec<(f64, f64)>> = vec![];
 
-    // Extract info for parallel async orderbook fetching
-    for leg in cycle {
-        let symbol_1 = format!("{}{}", leg.to, leg.from);
-        let symbol_2 = format!("{}{}", leg.from, leg.to);
-        let symbol = if exchange.symbols().contains_key(symbol_1.as_str()) { symbol_1 } else { symbol_2 };
-        let direction = if symbol.starts_with(leg.from.as_str()) { 
-            Direction::Forward // Uses Asks orderbooks
-        } else { 
-            Direction::Reverse // Uses Bids orderbooks
-        };
-
-        symbols.push(symbol);
-        directions.push(direction);
-    }
-
-    // Build futures for orderbook asyncronous extraction
-    let futures: Vec<_> = symbols.iter().zip(directions.iter())
-        .map(|(symbol, direction)| exchange.get_orderbook_depth(symbol.as_str(), direction))
+    // Build futures for orderbook asyncronous extraction (Forward uses bids, Reverse uses asks)
+    let futures: Vec<_> = cycle.iter()
+        .map(|leg| exchange.get_orderbook_depth(leg.symbol.as_str(), &leg.direction))
         .collect();
 
     // Call api for orderbooks
@@ -188,28 +142,30 @@ where T: BellmanFordEx + ExchangeData + ApiCalls
     }
 
     // Calculate Arbitrage
-    let Some(real_rate) = calculate_arbitrage(&orderbooks, &symbols, &directions, budget) else { return None };
+    let real_rate = calculate_arbitrage(&orderbooks, cycle, budget, exchange.fees())?;
 
     // Return result
-    Some((real_rate, symbols, directions, budget))
+    Some((real_rate, budget))
 }
 
 /// Store Arb
 /// Stores Arb found in table for later analysis
-pub fn store_arb_cycle(cycle: &Vec<Edge>, arb_rate: f64, arb_surface: f64) -> Result<(), SmartError> {
+pub fn store_arb_cycle(cycle: &[Edge], arb_rate: f64, arb_surface: f64) -> Result<(), SmartError> {
 
-    // Get unique assets
-    let mut assets_hs: HashSet<String> = HashSet::new();
-    for leg in cycle {
-        assets_hs.insert(leg.from.clone());
-        assets_hs.insert(leg.to.clone());
-    }
+    // Get assets in trading order
+    let arb_assets: Vec<&String> = cycle.iter().map(|leg| &leg.from).collect();
+
+    // Get symbol and side traded on each leg
+    let arb_legs: String = cycle.iter()
+        .map(|leg| format!("{}:{}", leg.symbol, leg.direction.side()))
+        .collect::<Vec<String>>()
+        .join(" ");
     
+    let cycle_id: String = CycleKey::from_cycle(cycle).to_string();
     let timestamp: u64 = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
     let arb_length = cycle.len();
-    let arb_assets: Vec<&String> = assets_hs.iter().collect();
     
-    let asset_0 = if arb_assets.len() > 0 { Some(arb_assets[0].to_owned()) } else { None };
+    let asset_0 = if !arb_assets.is_empty() { Some(arb_assets[0].to_owned()) } else { None };
     let asset_1 = if arb_assets.len() > 1 { Some(arb_assets[1].to_owned()) } else { None };
     let asset_2 = if arb_assets.len() > 2 { Some(arb_assets[2].to_owned()) } else { None };
     let asset_3 = if arb_assets.len() > 3 { Some(arb_assets[3].to_owned()) } else { None };
@@ -231,16 +187,17 @@ pub fn store_arb_cycle(cycle: &Vec<Edge>, arb_rate: f64, arb_surface: f64) -> Re
         asset_4,
         asset_5,
         asset_6,
-        asset_7
+        asset_7,
+        arb_legs,
+        cycle_id
     };
 
-    // Save data
-    let current_dir = std::env::current_dir()?;
-    let base_path = current_dir.to_str().unwrap();
+    // Save data, tests write to the temp dir so runs never touch the working tree
+    let base_dir = if cfg!(test) { std::env::temp_dir() } else { std::env::current_dir()? };
+    let base_path = base_dir.to_str().unwrap();
     let file_path = format!("{}/arbitrage_data.csv", base_path);
     let file_exists = std::path::Path::new(file_path.as_str()).exists();
     let file: std::fs::File = OpenOptions::new()
-        .write(true)
         .append(true)
         .create(true)
         .open(file_path)?;
@@ -255,30 +212,66 @@ pub fn store_arb_cycle(cycle: &Vec<Edge>, arb_rate: f64, arb_surface: f64) -> Re
 
 /// Calculate Arbitrage Surface Rate
 /// Calculates the surface rate of an arbitrage opportunity
-pub fn calculate_arbitrage_surface_rate(cycle: &Vec<Edge>) -> f64 {
+pub fn calculate_arbitrage_surface_rate(cycle: &[Edge]) -> f64 {
     cycle.iter().fold(1.0, |acc, edge| acc * f64::exp(-edge.weight)) - 1.0
 }
 
 /// Arb Scanner
 /// Scans and executes (if requested) for arbitrage
 pub async fn arb_scanner() -> Result<(), SmartError> {
+    arb_scanner_with_config(BinanceConfig::from_constants()).await
+}
+
+/// Arb Scanner With Config
+/// Runs the scanner against the Binance environment in config, i.e. the testnet or a mock server
+pub async fn arb_scanner_with_config(config: BinanceConfig) -> Result<(), SmartError> {
     println!("scanning for abitrage...");
+    let mut cooldown = CycleCooldown::new(Duration::from_millis(CYCLE_COOLDOWN_MS));
+    let paper_balances = paper_balance_sheet(&PAPER_BALANCES);
+
+    // One exchange for the whole scan so connections, limiter usage and cached orderbooks carry over
+    let mut exchange = Binance::unloaded(config);
 
     loop {
         std::thread::sleep(Duration::from_millis(50));
 
-        let exchange = Binance::new().await;
-        let cycles = exchange.run_bellman_ford_multi();
+        // Guard: Ensure exchange data is current, a ban is waited out by the rate limiter rather than resent
+        if let Err(e) = exchange.refresh().await {
+            eprintln!("Skipping scan: {}", e);
+            continue;
+        }
+
+        // Rebuild edges at depth for symbols on top of book candidate cycles
+        if GRAPH_MODE == GraphMode::Vwap {
+            let mut candidate_symbols: Vec<String> = exchange.run_bellman_ford_bounded().iter()
+                .flat_map(|cycle| cycle.iter().map(|leg| leg.symbol.clone()))
+                .collect();
+            candidate_symbols.sort();
+            candidate_symbols.dedup();
+
+            exchange.refresh_orderbooks(&candidate_symbols).await;
+            exchange.rebuild_exchange_rates();
+        }
+
+        let cycles = exchange.run_bellman_ford_bounded();
+
+        // Export what the detector saw for debugging
+        if let Some(dir) = GRAPH_EXPORT_DIR {
+            let bf = BellmanFord::new(&exchange.exchange_rates).prune_dead_ends();
+            GraphSnapshot::new(&bf, &cycles).save(dir, "graph_snapshot")?;
+        }
+
         for cycle in cycles {
 
+            // Guard: Ensure cycle was not just acted on
+            let cycle_key = CycleKey::from_cycle(&cycle);
+            if cooldown.is_cooling_down(&cycle_key) { continue; }
+
             print!("\ranalyzing cycle of length {}...", cycle.len());
             std::io::stdout().flush().unwrap();
 
-            // Guard: Ensure cycle length
-            if cycle.len() > MAX_CYCLE_LENGTH { continue; }
-
             let arb_opt = validate_arbitrage_cycle(&cycle, &exchange).await;
-            if let Some((arb_rate, symbols, directions, budget)) = arb_opt {
+            if let Some((arb_rate, budget)) = arb_opt {
 
                 // Guard: Ensure arb rate
                 dbg!(&arb_rate);
@@ -287,6 +280,7 @@ pub async fn arb_scanner() -> Result<(), SmartError> {
                 // Guard: Ensure from asset is ipart of Holding Assets
                 let from_asset = cycle[0].from.as_str();
                 if !ASSET_HOLDINGS.contains(&from_asset) { panic!("Error: Asset holdings do not include symbol") }
+                cooldown.mark(cycle_key);
                 
                 // Execute and get store trigger
                 let (is_store, is_trade) = match MODE {
@@ -297,16 +291,17 @@ pub async fn arb_scanner() -> Result<(), SmartError> {
                 // !!! PLACE TRADE !!!
                 if is_trade {
                     println!("\nPlacing trade...");
-                    let result = execute_arbitrage_cycle(
-                        budget,
-                        &cycle,
-                        &symbols,
-                        &directions,
-                        &exchange
-                    ).await;
+                    let result = if IS_PAPER_TRADE {
+                        let paper_exchange = PaperExchange::new(exchange.clone(), paper_balances.clone());
+                        execute_arbitrage_cycle(budget, &cycle, &paper_exchange).await
+                    } else {
+                        execute_arbitrage_cycle(budget, &cycle, &exchange).await
+                    };
                     
+                    // Guard: Only stop the bot when the account may be left in an unknown state
                     if let Err(e) = result {
-                        panic!("Failed to place trade: {:?}", e);
+                        if e.is_fatal() { panic!("Failed to place trade: {:?}", e); }
+                        eprintln!("Trade stopped: {}", e);
                     }
                 }
 
@@ -323,12 +318,14 @@ pub async fn arb_scanner() -> Result<(), SmartError> {
  #[cfg(test)]
  mod test {
     use super::*;
+    use crate::exchanges::mock_binance::MockBinance;
+    use crate::exchanges::snapshot::BinanceSnapshot;
+    use crate::models::OrderBook;
 
     #[tokio::test]
     async fn it_calculates_weighted_price_metrics() {
-        std::thread::sleep(Duration::from_millis(100));
-        let exchange = Binance::new().await;
-        let symbol: &str = "BTCUSDT";
+        let exchange = BinanceSnapshot::new().await;
+        let symbol: &str = "ETHUSDT";
         let budget: f64 = 50.0; // USDT
         let direction = Direction::Reverse;
         let orderbook = exchange.get_orderbook_depth(symbol, &direction).await.unwrap();
@@ -347,17 +344,113 @@ pub async fn arb_scanner() -> Result<(), SmartError> {
         };
     }
 
+    #[test]
+    fn it_cools_down_a_cycle_under_any_rotation() {
+        let leg = |from: &str, to: &str| Edge { from: from.to_string(), to: to.to_string(), weight: 0.0, symbol: format!("{}{}", from, to), direction: Direction::Forward };
+        let cycle = vec![leg("USDT", "BTC"), leg("BTC", "ETH"), leg("ETH", "USDT")];
+        let rotated = vec![leg("BTC", "ETH"), leg("ETH", "USDT"), leg("USDT", "BTC")];
+
+        let mut cooldown = CycleCooldown::new(Duration::from_secs(60));
+        assert!(!cooldown.is_cooling_down(&CycleKey::from_cycle(&cycle)));
+        cooldown.mark(CycleKey::from_cycle(&cycle));
+        assert!(cooldown.is_cooling_down(&CycleKey::from_cycle(&rotated)));
+
+        let mut expired = CycleCooldown::new(Duration::ZERO);
+        expired.mark(CycleKey::from_cycle(&cycle));
+        assert!(!expired.is_cooling_down(&CycleKey::from_cycle(&rotated)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn it_scans_mock_server_end_to_end() {
+        let mock = MockBinance::start(&[("USDT", 100.0)]);
+
+        // Top of book shows SOLETH rich but depth is at fair value so nothing passes validation or gets stored
+        let fair_book = OrderBook { bids: vec![(0.05, 100.0)], asks: vec![(0.05001, 100.0)], timestamp: 0 };
+        mock.state.lock().unwrap().snapshot.orderbooks.insert("SOLETH".to_string(), fair_book);
+
+        let scanner = tokio::spawn(arb_scanner_with_config(mock.config.clone()));
+        let waiter = mock.clone();
+        let is_validated = tokio::task::spawn_blocking(move || waiter.wait_for("/api/v3/depth?symbol=SOLETH", 1, Duration::from_secs(10)))
+            .await
+            .unwrap();
+        scanner.abort();
+
+        assert!(is_validated);
+        assert!(!mock.requests_to("/api/v3/exchangeInfo").is_empty());
+        assert!(mock.requests_to("/api/v3/order").is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn it_keeps_scanning_through_a_ban_without_resending() {
+        let mock = MockBinance::start(&[("USDT", 100.0)]);
+        {
+            // Depth at fair value so nothing gets stored once scanning resumes
+            let mut state = mock.state.lock().unwrap();
+            let fair_book = OrderBook { bids: vec![(0.05, 100.0)], asks: vec![(0.05001, 100.0)], timestamp: 0 };
+            state.snapshot.orderbooks.insert("SOLETH".to_string(), fair_book);
+            state.retry_after_s = 3;
+            let banned = (418, serde_json::json!({ "code": -1003, "msg": "Way too many requests; IP banned." }));
+            state.transient_errors.insert("/api/v3/ticker/price".to_string(), vec![banned].into());
+        }
+
+        let scanner = tokio::spawn(arb_scanner_with_config(mock.config.clone()));
+        let waiter = mock.clone();
+        let is_banned = tokio::task::spawn_blocking(move || waiter.wait_for("/api/v3/ticker/price", 1, Duration::from_secs(10)))
+            .await
+            .unwrap();
+        assert!(is_banned);
+
+        // Later scans are held by the limiter instead of going back to Binance
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert_eq!(mock.requests_to("/api/v3/exchangeInfo").len(), 1);
+        assert!(!scanner.is_finished());
+
+        // Scanning resumes once Retry-After has passed
+        let waiter = mock.clone();
+        let is_resumed = tokio::task::spawn_blocking(move || waiter.wait_for("/api/v3/exchangeInfo", 2, Duration::from_secs(10)))
+            .await
+            .unwrap();
+        scanner.abort();
+        assert!(is_resumed);
+    }
+
+    #[test]
+    fn it_deducts_fees_from_arbitrage_rate() {
+        let orderbooks = vec![
+            vec![(0.05, 100.0)],    // ETHBTC bids
+            vec![(2000.0, 100.0)],  // ETHUSDT bids
+            vec![(40000.0, 100.0)], // BTCUSDT asks
+        ];
+        let leg = |from: &str, to: &str, symbol: &str, direction: Direction| Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+            weight: 0.0,
+            symbol: symbol.to_string(),
+            direction
+        };
+        let cycle = vec![
+            leg("BTC", "ETH", "ETHBTC", Direction::Reverse),
+            leg("ETH", "USDT", "ETHUSDT", Direction::Forward),
+            leg("USDT", "BTC", "BTCUSDT", Direction::Reverse),
+        ];
+
+        let no_fees = FeeSchedule { default_taker_fee: 0.0, overrides: std::collections::HashMap::new(), is_bnb_discount: false };
+        let fees = FeeSchedule { default_taker_fee: 0.001, overrides: std::collections::HashMap::new(), is_bnb_discount: false };
+
+        let gross_rate = calculate_arbitrage(&orderbooks, &cycle, 0.01, &no_fees).unwrap();
+        let net_rate = calculate_arbitrage(&orderbooks, &cycle, 0.01, &fees).unwrap();
+        assert!((gross_rate - 1.0).abs() < 1e-9);
+        assert!((net_rate - 0.999_f64.powi(3)).abs() < 1e-9);
+    }
+
     #[tokio::test]
     async fn it_validates_arbitrage_cycle() {
-        std::thread::sleep(Duration::from_millis(100));
-        let exchange = Binance::new().await;
+        let exchange = BinanceSnapshot::new().await;
         let cycle = exchange.run_bellman_ford_single().unwrap();
         let result = validate_arbitrage_cycle(&cycle, &exchange).await;
         match result {
-            Some((real_rate, symbols, directions, budget)) => {
+            Some((real_rate, budget)) => {
                 assert!(real_rate > 0.0);
-                assert!(symbols.len() > 0);
-                assert!(directions.len() > 0);
                 assert!(budget > 0.0);
             },
             None => println!("No real arbitrage opportunity")
@@ -366,8 +459,7 @@ pub async fn arb_scanner() -> Result<(), SmartError> {
 
     #[tokio::test]
     async fn it_stores_an_arb_cycle() {
-        std::thread::sleep(Duration::from_millis(100));
-        let exchange = Binance::new().await;
+        let exchange = BinanceSnapshot::new().await;
         let cycle = exchange.run_bellman_ford_single().unwrap();
         let _result: () = store_arb_cycle(&cycle, 1.1, 0.1).unwrap();
     }
@@ -1,17 +1,19 @@
 use super::bellmanford::Edge;
-use super::constants::{MAX_CYCLE_LENGTH, MODE};
-use super::helpers::validate_quantity;
+use super::constants::{HTTP_RETRY_BACKOFF_MS, MAX_CYCLE_LENGTH, MODE, ORDER_MAX_RETRIES};
+use super::helpers::{to_decimal, validate_quantity};
 use super::models::{Direction, SmartError, Mode};
 use super::traits::{ApiCalls, BellmanFordEx, ExchangeData};
 
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::time::Duration;
+
 /// Execute Arbitrage Cycle
 /// Executes Arbitrage Cycle.
 /// Using panics as checks should happen before this function is called.
 pub async fn execute_arbitrage_cycle<T>(
   budget: f64,
-  cycle: &Vec<Edge>,
-  symbols: &Vec<String>, 
-  directions: &Vec<Direction>,
+  cycle: &[Edge],
   exchange: &T
 ) -> Result<(), SmartError> 
   where T: BellmanFordEx + ExchangeData + ApiCalls 
@@ -24,28 +26,43 @@ pub async fn execute_arbitrage_cycle<T>(
   };
   if !is_trade { panic!("Tried to place trade when Mode not set to trading") }
 
+  place_cycle_orders(budget, cycle, exchange).await
+}
+
+/// Place Cycle Orders
+/// Places a market order for every leg, each sized from what the previous leg received
+/// Mode is not checked here, call execute_arbitrage_cycle from the bot
+/// A failure after the first fill is CycleIncomplete, naming the leg and the asset the funds were left in
+pub async fn place_cycle_orders<T>(
+  budget: f64,
+  cycle: &[Edge],
+  exchange: &T
+) -> Result<(), SmartError> 
+  where T: BellmanFordEx + ExchangeData + ApiCalls 
+{
+
   // Guard: Ensure correct cycle length
-  if symbols.len() > MAX_CYCLE_LENGTH {
+  if cycle.len() > MAX_CYCLE_LENGTH {
     panic!("Error: Too many cycles. Max length set to {} in concstants", MAX_CYCLE_LENGTH)
   }
 
-  // Guard: Ensure symbols len
-  if symbols.len() < 3 {
+  // Guard: Ensure cycle len
+  if cycle.len() < 3 {
     panic!("Error: Trade attempted when not enough cycle legs to complete trade")
   }
 
-  // Guard: Ensure symbols lenght matches directions length
-  assert_eq!(symbols.len(), directions.len());
+  // Guard: Ensure every leg passes the exchange filters before the first order is sent
+  validate_cycle_orders(budget, cycle, exchange).map_err(SmartError::Validation)?;
   
   // Initialize
-  let mut quantity: f64 = budget;
+  let mut quantity: Decimal = to_decimal(budget);
   let info_symbols = exchange.symbols();
   let general_prices = exchange.prices();
+  let symbols: Vec<&str> = cycle.iter().map(|leg| leg.symbol.as_str()).collect();
 
-  for i in 0..symbols.len() {
-    let symbol = &symbols[i];
-    let direction = &directions[i];
-    let leg = &cycle[i];
+  for (i, leg) in cycle.iter().enumerate() {
+    let symbol = leg.symbol.as_str();
+    let direction = &leg.direction;
 
     // Execute Trade
     println!("---");
@@ -56,49 +73,232 @@ pub async fn execute_arbitrage_cycle<T>(
     println!("side: {}", direction.side());
     println!("initial quantity: {}", quantity);
     println!("---");
+
+    // Once a leg has filled, any failure leaves funds in an intermediate asset
+    let stop = |e: SmartError| match i {
+      0 => e,
+      _ => SmartError::CycleIncomplete { leg: i, asset: leg.from.clone(), source: Box::new(e) }
+    };
     
     // Adjust quantity if lower asset balance
     let asset: String = leg.from.clone();
-    let asset_balance: f64 = exchange.get_asset_account_balance(&asset).await.expect("Failed to get asset balance");
-    if asset_balance == 0.0 { panic!("No trading amount available for this asset") }
+    let asset_balance: Decimal = exchange.get_asset_account_balance(&asset).await.map_err(stop)?;
+    if asset_balance.is_zero() {
+      return Err(stop(SmartError::Validation(format!("No trading amount available for {} in {:?}", asset, symbols))));
+    }
     if asset_balance < quantity { quantity = asset_balance };
 
     // Adj quantity for formatting
     let symbol_info = info_symbols.get(symbol).expect("Failed to extract symbol during live trade");
-    let general_price = general_prices[symbol];
-    quantity = match validate_quantity(&symbol_info, quantity, general_price, direction) {
+    let general_price = to_decimal(general_prices[symbol]);
+    let book_price = get_book_price(exchange, symbol, direction).unwrap_or(general_price);
+    quantity = match validate_quantity(symbol_info, quantity, general_price, book_price, direction) {
       Ok(qty) => qty,
-      Err(_e) => {
-        dbg!(&_e);
-        quantity
-      }
+      Err(e) => return Err(stop(SmartError::Validation(format!("{} in {:?}: {}", symbol, symbols, e))))
     };
     
     // PLACE TRADE
-    let result = exchange.place_market_order(symbol, direction, quantity).await;
-
-    // Update next quantity to match what was received
-    match result {
-      Ok((status, base_amount_out, quote_amount_out)) => {
-
-        // Guard: Ensure success else panic
-        if base_amount_out == 0.0 || status.as_str() != "FILLED" {
-          panic!("Order not filled: {} in {:?}, status: {}, base_amount: {}, quote_amouunt: {}", symbol, symbols, status, base_amount_out, quote_amount_out);
-        }
-        
-        // Update quantity for next trade
-        if i < symbols.len() - 1 {
-          match direction {
-            Direction::Forward => quantity = quote_amount_out,
-            Direction::Reverse => quantity = base_amount_out,
-          }
-        }
-      },
-      Err(e) => {
-        panic!("Order not filled: {} in {:?}: e: {:?}", symbol, symbols, e);
+    let (status, base_amount_out, quote_amount_out) = place_order_with_retry(exchange, symbol, direction, quantity).await.map_err(stop)?;
+
+    // Guard: Ensure order filled
+    if base_amount_out.is_zero() || status.as_str() != "FILLED" {
+      return Err(stop(SmartError::Runtime(format!("Order not filled: {} in {:?}, status: {}, base_amount: {}, quote_amount: {}", symbol, symbols, status, base_amount_out, quote_amount_out))));
+    }
+    
+    // Update quantity for next trade
+    if i < cycle.len() - 1 {
+      match direction {
+        Direction::Forward => quantity = quote_amount_out,
+        Direction::Reverse => quantity = base_amount_out,
       }
     }
   }
 
   Ok(())
 }
+
+/// Place Order With Retry
+/// Resends an order Binance rejected without executing, i.e. a timestamp outside recvWindow
+/// Every other error is returned as is, an order is never resent when it may have executed
+async fn place_order_with_retry<T>(exchange: &T, symbol: &str, direction: &Direction, quantity: Decimal) -> Result<(String, Decimal, Decimal), SmartError>
+  where T: ApiCalls
+{
+  let mut attempt = 0;
+  loop {
+    match exchange.place_market_order(symbol, direction, quantity).await {
+      Err(SmartError::Binance(e)) if e.kind().is_retryable() && attempt < ORDER_MAX_RETRIES => {
+        eprintln!("Retrying order {} after: {}", symbol, e.msg);
+        attempt += 1;
+        tokio::time::sleep(Duration::from_millis(HTTP_RETRY_BACKOFF_MS * 2u64.pow(attempt - 1))).await;
+      },
+      result => return result
+    }
+  }
+}
+
+/// Validate Cycle Orders
+/// Sizes every leg from the budget and the cycle rates and checks each against the symbol filters
+/// Also checks that no symbol needs more orders than MAX_NUM_ORDERS allows
+fn validate_cycle_orders<T>(budget: f64, cycle: &[Edge], exchange: &T) -> Result<(), String>
+  where T: ExchangeData
+{
+  let mut quantity = to_decimal(budget);
+  let mut orders_per_symbol: HashMap<&str, u32> = HashMap::new();
+  for leg in cycle {
+    let symbol = leg.symbol.as_str();
+    let symbol_info = exchange.symbols().get(symbol).ok_or(format!("Unknown symbol {}", symbol))?;
+    let general_price = to_decimal(*exchange.prices().get(symbol).ok_or(format!("No price for {}", symbol))?);
+    let book_price = get_book_price(exchange, symbol, &leg.direction).unwrap_or(general_price);
+    validate_quantity(symbol_info, quantity, general_price, book_price, &leg.direction)?;
+
+    let orders = orders_per_symbol.entry(symbol).or_default();
+    *orders += 1;
+    if symbol_info.max_num_orders.is_some_and(|max_num_orders| *orders > max_num_orders) {
+      return Err(format!("Too many orders for {}", symbol));
+    }
+
+    // Weight is -log10 of the net rate
+    quantity *= to_decimal(10f64.powf(-leg.weight));
+  }
+  Ok(())
+}
+
+/// Get Book Price
+/// Best bid for a sell and best ask for a buy
+fn get_book_price<T: ExchangeData>(exchange: &T, symbol: &str, direction: &Direction) -> Option<Decimal> {
+  let book_ticker = exchange.book_tickers().get(symbol)?;
+  match direction {
+    Direction::Forward => Some(to_decimal(book_ticker.best_bid_price)),
+    Direction::Reverse => Some(to_decimal(book_ticker.best_ask_price)),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::exchanges::binance::Binance;
+  use crate::exchanges::mock_binance::MockBinance;
+  use crate::models::BinanceErrorKind;
+
+  async fn get_test_cycle(exchange: &Binance) -> Vec<Edge> {
+    exchange.run_bellman_ford_bounded().into_iter()
+      .find(|cycle| cycle[0].from == "USDT")
+      .expect("Expected a USDT cycle in the fixture")
+  }
+
+  #[tokio::test]
+  async fn it_places_every_leg_against_mock_server() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+
+    place_cycle_orders(25.0, &cycle, &exchange).await.unwrap();
+
+    let state = mock.state.lock().unwrap();
+    assert_eq!(state.orders.len(), cycle.len());
+    for (order, leg) in state.orders.iter().zip(&cycle) {
+      assert!(order.contains(&format!("symbol={}&side={}", leg.symbol, leg.direction.side())));
+    }
+    assert!(state.balances["USDT"] > 100.0);
+  }
+
+  #[tokio::test]
+  async fn it_stops_when_an_order_is_rejected() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+    mock.state.lock().unwrap().fills.push_back(MockBinance::order_response(&cycle[0].symbol, cycle[0].direction.side(), "EXPIRED", 0.0, 0.0));
+
+    let result = place_cycle_orders(25.0, &cycle, &exchange).await;
+    assert!(matches!(result, Err(SmartError::Runtime(msg)) if msg.starts_with("Order not filled")));
+    assert_eq!(mock.state.lock().unwrap().orders.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn it_reports_where_funds_are_left_when_a_later_leg_fails() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+
+    // First leg fills on the exchange but the account shows nothing to trade on the second
+    mock.state.lock().unwrap().fills.push_back(MockBinance::order_response(&cycle[0].symbol, cycle[0].direction.side(), "FILLED", 0.1, 25.0));
+
+    let error = place_cycle_orders(25.0, &cycle, &exchange).await.unwrap_err();
+    assert!(error.is_fatal());
+    match error {
+      SmartError::CycleIncomplete { leg, asset, source } => {
+        assert_eq!((leg, asset), (1, cycle[1].from.clone()));
+        assert!(matches!(*source, SmartError::Validation(_)));
+      },
+      error => panic!("Expected an incomplete cycle, got {:?}", error)
+    }
+    assert_eq!(mock.state.lock().unwrap().orders.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn it_resends_an_order_rejected_for_its_timestamp() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+    let timestamp = (400, serde_json::json!({ "code": -1021, "msg": "Timestamp for this request is outside of the recvWindow." }));
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/order".to_string(), vec![timestamp].into());
+
+    place_cycle_orders(25.0, &cycle, &exchange).await.unwrap();
+    assert_eq!(mock.requests_to("/api/v3/order").len(), cycle.len() + 1);
+    assert_eq!(mock.state.lock().unwrap().orders.len(), cycle.len());
+  }
+
+  #[tokio::test]
+  async fn it_never_resends_an_order_with_an_unknown_execution_status() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+    let unavailable = (503, serde_json::json!({ "code": -1001, "msg": "Internal error; unable to process your request." }));
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/order".to_string(), vec![unavailable].into());
+
+    match place_cycle_orders(25.0, &cycle, &exchange).await {
+      Err(SmartError::Binance(e)) => {
+        assert_eq!(e.kind(), BinanceErrorKind::ExecutionUnknown);
+        assert!(SmartError::Binance(e).is_fatal());
+      },
+      result => panic!("Expected an unknown execution status, got {:?}", result)
+    }
+    assert_eq!(mock.requests_to("/api/v3/order").len(), 1);
+  }
+
+  #[tokio::test]
+  async fn it_stops_without_panicking_on_insufficient_balance() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+    let balance = (400, serde_json::json!({ "code": -2010, "msg": "Account has insufficient balance for requested action." }));
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/order".to_string(), vec![balance].into());
+
+    match place_cycle_orders(25.0, &cycle, &exchange).await {
+      Err(SmartError::Binance(e)) => {
+        assert_eq!(e.kind(), BinanceErrorKind::InsufficientBalance);
+        assert!(!SmartError::Binance(e).is_fatal());
+      },
+      result => panic!("Expected an insufficient balance error, got {:?}", result)
+    }
+    assert_eq!(mock.requests_to("/api/v3/order").len(), 1);
+  }
+
+  #[tokio::test]
+  async fn it_rejects_a_cycle_before_the_first_order() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let mut exchange = Binance::with_config(mock.config.clone()).await;
+    let cycle = get_test_cycle(&exchange).await;
+    let last_leg = cycle.last().unwrap();
+    exchange.symbols.get_mut(&last_leg.symbol).unwrap().notional.min_notional = Decimal::from(1_000_000_000);
+
+    assert!(place_cycle_orders(25.0, &cycle, &exchange).await.is_err());
+    assert!(mock.state.lock().unwrap().orders.is_empty());
+
+    // MAX_NUM_ORDERS
+    exchange.symbols.get_mut(&last_leg.symbol).unwrap().notional.min_notional = Decimal::ZERO;
+    assert!(validate_cycle_orders(25.0, &cycle, &exchange).is_ok());
+    exchange.symbols.get_mut(&cycle[0].symbol).unwrap().max_num_orders = Some(0);
+    assert!(validate_cycle_orders(25.0, &cycle, &exchange).unwrap_err().contains("Too many orders"));
+  }
+}
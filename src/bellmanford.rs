@@ -1,78 +1,247 @@
-use std::collections::{HashMap, HashSet};
+use super::models::{DirectedRate, Direction};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Edge
+/// Named edge returned to callers once a cycle has been mapped back to assets
+/// Carries the exact symbol and direction the rate was built from
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Edge {
   pub from: String,
   pub to: String,
   pub weight: f64,
+  pub symbol: String,
+  pub direction: Direction,
+}
+
+/// Indexed Edge
+/// Compact edge between interned currency ids used during relaxation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexedEdge {
+  pub from: usize,
+  pub to: usize,
+  pub weight: f64,
+  pub symbol: usize,
+  pub direction: Direction,
+}
+
+/// Route
+/// Conversion path between two currencies with its effective rate (net of fees when rates are)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+  pub edges: Vec<Edge>,
+  pub rate: f64,
+}
+
+/// Cycle Key
+/// Canonical identity of a cycle: assets rotated to start at the smallest asset, with the symbol traded on each leg
+/// Rotations of the same loop share a key regardless of where detection entered it or the weights at the time
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CycleKey {
+  pub assets: Vec<String>,
+  pub symbols: Vec<String>,
+}
+
+impl CycleKey {
+  pub fn from_cycle(cycle: &[Edge]) -> Self {
+    let start = cycle.iter().enumerate().min_by_key(|(_, edge)| &edge.from).map(|(i, _)| i).unwrap_or(0);
+    let rotated = cycle[start..].iter().chain(cycle[..start].iter());
+    let (assets, symbols) = rotated.map(|edge| (edge.from.clone(), edge.symbol.clone())).unzip();
+    Self { assets, symbols }
+  }
+}
+
+/// Cycle Key Display
+/// Stable id used in storage and logs, i.e. BTC>ETH>USDT|ETHBTC>ETHUSDT>BTCUSDT
+impl fmt::Display for CycleKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}|{}", self.assets.join(">"), self.symbols.join(">"))
+  }
 }
 
 #[derive(Debug)]
 pub struct BellmanFord {
-  pub edges: Vec<Edge>,
-  pub currency_index_map: HashMap<String, usize>
+  pub edges: Vec<IndexedEdge>,
+  pub currencies: Vec<String>,
+  pub currency_index_map: HashMap<String, usize>,
+  pub symbols: Vec<String>
 }
 
 impl BellmanFord {
-  pub fn new(exchange_rates: &Vec<(String, String, f64)>) -> Self {
-    let mut edges = Vec::new();
+  pub fn new(exchange_rates: &[DirectedRate]) -> Self {
+    let mut edges = Vec::with_capacity(exchange_rates.len());
+    let mut currencies = Vec::new();
     let mut currency_index_map = HashMap::new();
-    let mut index = 0;
+    let mut symbols = Vec::new();
+    let mut symbol_index_map = HashMap::new();
 
-    for (from, to, rate) in exchange_rates {
-      edges.push(Edge {
-        from: from.clone(),
-        to: to.clone(),
-        weight: -f64::log10(*rate),
+    for exchange_rate in exchange_rates {
+      let from_index = Self::intern(&exchange_rate.from, &mut currencies, &mut currency_index_map);
+      let to_index = Self::intern(&exchange_rate.to, &mut currencies, &mut currency_index_map);
+      let symbol_index = Self::intern(&exchange_rate.symbol, &mut symbols, &mut symbol_index_map);
+      edges.push(IndexedEdge {
+        from: from_index,
+        to: to_index,
+        weight: -f64::log10(exchange_rate.rate),
+        symbol: symbol_index,
+        direction: exchange_rate.direction,
       });
+    }
 
-      if !currency_index_map.contains_key(from) {
-        currency_index_map.insert(from.clone(), index);
-        index += 1;
-      }
-      if !currency_index_map.contains_key(to) {
-        currency_index_map.insert(to.clone(), index);
-        index += 1;
+    Self { edges, currencies, currency_index_map, symbols }
+  }
+
+  /// Prune Dead Ends
+  /// Repeatedly drops currencies with fewer than two distinct neighbours
+  /// Such currencies can only ever be part of a two-edge reciprocation, never a cycle
+  pub fn prune_dead_ends(&self) -> BellmanFord {
+    let number_of_currencies = self.currencies.len();
+    let mut neighbours: Vec<HashSet<usize>> = vec![HashSet::new(); number_of_currencies];
+    for edge in &self.edges {
+      if edge.from == edge.to { continue; }
+      neighbours[edge.from].insert(edge.to);
+      neighbours[edge.to].insert(edge.from);
+    }
+
+    let mut alive = vec![true; number_of_currencies];
+    let mut queue: Vec<usize> = (0..number_of_currencies).filter(|&v| neighbours[v].len() < 2).collect();
+    while let Some(v) = queue.pop() {
+      if !alive[v] { continue; }
+      alive[v] = false;
+      for u in std::mem::take(&mut neighbours[v]) {
+        neighbours[u].remove(&v);
+        if alive[u] && neighbours[u].len() < 2 {
+          queue.push(u);
+        }
       }
     }
 
-    Self { edges, currency_index_map }
+    let vertices: Vec<usize> = (0..number_of_currencies).filter(|&v| alive[v]).collect();
+    self.subgraph(&vertices)
   }
 
-  /// Find Negative Cycle
-  /// Finds a single negative cycle
-  pub fn find_negative_cycle(&self) -> Option<Vec<Edge>> {
-    let number_of_currencies = self.currency_index_map.len();
-    let mut distance = vec![f64::INFINITY; number_of_currencies];
-    let mut predecessor: Vec<Option<Edge>> = vec![None; number_of_currencies];
+  /// Strongly Connected Components
+  /// Tarjan's algorithm (iterative) over the currency graph
+  pub fn strongly_connected_components(&self) -> Vec<Vec<usize>> {
+    let number_of_currencies = self.currencies.len();
+    let adjacency = self.adjacency();
+    let mut index = vec![usize::MAX; number_of_currencies];
+    let mut lowlink = vec![0; number_of_currencies];
+    let mut on_stack = vec![false; number_of_currencies];
+    let mut stack: Vec<usize> = Vec::new();
+    let mut components: Vec<Vec<usize>> = Vec::new();
+    let mut next_index = 0;
 
-    distance[0] = 0.0; // Assuming 0 as the source vertex
+    for root in 0..number_of_currencies {
+      if index[root] != usize::MAX { continue; }
 
-    for _ in 0..number_of_currencies {
-      let mut update = false; // Flag to check if any update happens in this iteration
+      index[root] = next_index;
+      lowlink[root] = next_index;
+      next_index += 1;
+      stack.push(root);
+      on_stack[root] = true;
 
-      for edge in &self.edges {
-          let u = self.get_currency_index(&edge.from);
-          let v = self.get_currency_index(&edge.to);
-          if distance[u] + edge.weight < distance[v] {
-            distance[v] = distance[u] + edge.weight;
-            predecessor[v] = Some(edge.clone());
-            update = true; // Update flag when a change occurs
+      // Explicit call stack of (vertex, next adjacency position)
+      let mut call_stack: Vec<(usize, usize)> = vec![(root, 0)];
+      while let Some(&(v, position)) = call_stack.last() {
+        if position < adjacency[v].len() {
+          call_stack.last_mut().unwrap().1 += 1;
+          let w = self.edges[adjacency[v][position]].to;
+          if index[w] == usize::MAX {
+            index[w] = next_index;
+            lowlink[w] = next_index;
+            next_index += 1;
+            stack.push(w);
+            on_stack[w] = true;
+            call_stack.push((w, 0));
+          } else if on_stack[w] {
+            lowlink[v] = lowlink[v].min(index[w]);
           }
-      }
+          continue;
+        }
 
-      // Early termination if no update in this iteration
-      if !update {
-        break;
+        call_stack.pop();
+        if let Some(&(parent, _)) = call_stack.last() {
+          lowlink[parent] = lowlink[parent].min(lowlink[v]);
+        }
+
+        // Root of a component
+        if lowlink[v] == index[v] {
+          let mut component = Vec::new();
+          while let Some(w) = stack.pop() {
+            on_stack[w] = false;
+            component.push(w);
+            if w == v { break; }
+          }
+          components.push(component);
+        }
       }
     }
 
+    components
+  }
+
+  /// Components
+  /// Dead ends pruned, then one graph per strongly connected component able to hold a cycle
+  pub fn components(&self) -> Vec<BellmanFord> {
+    let pruned = self.prune_dead_ends();
+    pruned.strongly_connected_components().iter()
+      .filter(|component| component.len() > 2)
+      .map(|component| pruned.subgraph(component))
+      .collect()
+  }
+
+  /// Subgraph
+  /// Graph induced by the given currencies, keeping edge weights and metadata exactly
+  pub fn subgraph(&self, vertices: &[usize]) -> BellmanFord {
+    let mut keep = vec![false; self.currencies.len()];
+    vertices.iter().for_each(|&v| keep[v] = true);
+
+    let mut edges = Vec::new();
+    let mut currencies = Vec::new();
+    let mut currency_index_map = HashMap::new();
+    let mut symbols = Vec::new();
+    let mut symbol_index_map = HashMap::new();
+
+    for edge in self.edges.iter().filter(|edge| keep[edge.from] && keep[edge.to]) {
+      edges.push(IndexedEdge {
+        from: Self::intern(&self.currencies[edge.from], &mut currencies, &mut currency_index_map),
+        to: Self::intern(&self.currencies[edge.to], &mut currencies, &mut currency_index_map),
+        weight: edge.weight,
+        symbol: Self::intern(&self.symbols[edge.symbol], &mut symbols, &mut symbol_index_map),
+        direction: edge.direction,
+      });
+    }
+
+    BellmanFord { edges, currencies, currency_index_map, symbols }
+  }
+
+  /// Find All Negative Cycles By Component
+  /// Runs find_all_negative_cycles on every pruned strongly connected component in parallel
+  pub fn find_all_negative_cycles_by_component(&self) -> Vec<Vec<Edge>> {
+    let results: Vec<Vec<Vec<Edge>>> = self.components().par_iter()
+      .map(|component| component.find_all_negative_cycles())
+      .collect();
+    Self::dedup_cycles(results.into_iter().flatten())
+  }
+
+  /// Dedup Cycles
+  /// Keeps the first of every named cycle sharing a canonical cycle key
+  pub fn dedup_cycles(cycles: impl IntoIterator<Item = Vec<Edge>>) -> Vec<Vec<Edge>> {
+    let mut seen: HashSet<CycleKey> = HashSet::new();
+    cycles.into_iter().filter(|cycle| seen.insert(CycleKey::from_cycle(cycle))).collect()
+  }
+
+  /// Find Negative Cycle
+  /// Finds a single negative cycle
+  pub fn find_negative_cycle(&self) -> Option<Vec<Edge>> {
+    let (distance, predecessor) = self.relax_edges(0, true);
+
     // Check for negative cycle
     for edge in &self.edges {
-      let u = self.get_currency_index(&edge.from);
-      let v = self.get_currency_index(&edge.to);
-      if distance[u] + edge.weight < distance[v] {
-        return Some(self.construct_cycle(v, &predecessor));
+      if distance[edge.from] + edge.weight < distance[edge.to] {
+        return Some(self.to_named_cycle(&self.construct_cycle(edge.to, &predecessor)));
       }
     }
 
@@ -82,98 +251,538 @@ impl BellmanFord {
   /// Find All Negative Cycles
   /// Find all negative cycles possible
   pub fn find_all_negative_cycles(&self) -> Vec<Vec<Edge>> {
-    let number_of_currencies = self.currency_index_map.len();
-    let mut distance = vec![f64::INFINITY; number_of_currencies];
-    let mut predecessor: Vec<Option<Edge>> = vec![None; number_of_currencies];
+    let cycles = self.find_all_negative_cycles_from(0);
+    cycles.iter().map(|cycle| self.to_named_cycle(cycle)).collect()
+  }
+
+  /// Find All Negative Cycles Parallel
+  /// Runs find_all_negative_cycles from every source currency at once, merging rotations of the same cycle
+  pub fn find_all_negative_cycles_parallel(&self, sources: &[&str]) -> Vec<Vec<Edge>> {
+    let source_indices: Vec<usize> = sources.iter()
+      .filter_map(|source| self.currency_index_map.get(*source).copied())
+      .collect();
+
+    let results: Vec<Vec<Vec<usize>>> = source_indices.par_iter()
+      .map(|&source| self.find_all_negative_cycles_from(source))
+      .collect();
+
+    let mut seen: HashSet<Vec<usize>> = HashSet::new();
+    results.into_iter()
+      .flatten()
+      .filter(|cycle| seen.insert(Self::rotation_key(cycle)))
+      .map(|cycle| self.to_named_cycle(&cycle))
+      .collect()
+  }
+
+  /// Find All Negative Cycles From
+  /// Relaxes from source and walks back every edge still able to relax, as edge indices
+  fn find_all_negative_cycles_from(&self, source: usize) -> Vec<Vec<usize>> {
+    let (distance, predecessor) = self.relax_edges(source, false);
     let mut visited_edges = HashSet::new();
+    let mut seen: HashSet<Vec<usize>> = HashSet::new();
 
-    distance[0] = 0.0; // Assuming 0 as the source vertex
+    // Check for negative cycles
+    let mut cycles: Vec<Vec<usize>> = Vec::new();
+    for edge in &self.edges {
+
+      // Skip the edge if it was part of a previously found cycle
+      if visited_edges.contains(&(edge.from, edge.to)) {
+        continue;
+      }
+
+      if distance[edge.from] + edge.weight < distance[edge.to] {
+        let cycle = self.construct_cycle(edge.to, &predecessor);
+        if !cycle.is_empty() {
+          // Mark all edges in the cycle as visited
+          for &edge_index in &cycle {
+            let cycle_edge = &self.edges[edge_index];
+            visited_edges.insert((cycle_edge.from, cycle_edge.to));
+          }
+
+          if seen.insert(Self::rotation_key(&cycle)) {
+            cycles.push(cycle);
+          }
+        }
+      }
+    }
+
+    cycles
+  }
+
+  /// Find Bounded Negative Cycles
+  /// Finds negative cycles of at most max_length hops passing through any of the source currencies
+  /// Each cycle is rotated to start (and end) at the source it was found from, most negative first
+  pub fn find_bounded_negative_cycles(&self, sources: &[&str], max_length: usize) -> Vec<Vec<Edge>> {
+    let mut seen: HashSet<Vec<usize>> = HashSet::new();
+    let mut cycles: Vec<(f64, Vec<usize>)> = Vec::new();
+
+    for source in sources {
+      let Some(&source_index) = self.currency_index_map.get(*source) else { continue };
+      for cycle in self.find_bounded_cycles_from(source_index, max_length) {
+
+        // Guard: Ensure rotation of a cycle already found from another source is not repeated
+        if !seen.insert(Self::rotation_key(&cycle)) { continue; }
+
+        cycles.push((self.cycle_weight(&cycle), cycle));
+      }
+    }
+
+    cycles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    cycles.iter().map(|(_, cycle)| self.to_named_cycle(cycle)).collect()
+  }
+
+  /// Enumerate Cycles
+  /// Lists every simple cycle of at most max_length hops through the source currencies
+  /// Rotations of the same cycle are removed and the top_n by product rate are returned
+  pub fn enumerate_cycles(&self, sources: &[&str], max_length: usize, top_n: usize) -> Vec<Vec<Edge>> {
+    let mut cycles: Vec<(f64, Vec<usize>)> = self.enumerate_cycle_indices(sources, max_length).into_iter()
+      .map(|cycle| (self.cycle_weight(&cycle), cycle))
+      .collect();
+
+    // Lowest total weight is the highest product rate
+    cycles.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    cycles.truncate(top_n);
+    cycles.iter().map(|(_, cycle)| self.to_named_cycle(cycle)).collect()
+  }
+
+  /// Enumerate Cycle Indices
+  /// Lists every simple cycle through the source currencies as edge indices, rotations removed
+  pub fn enumerate_cycle_indices(&self, sources: &[&str], max_length: usize) -> Vec<Vec<usize>> {
+    let adjacency = self.adjacency();
+    let mut seen: HashSet<Vec<usize>> = HashSet::new();
+    let mut cycles: Vec<Vec<usize>> = Vec::new();
+
+    for source in sources {
+      let Some(&source_index) = self.currency_index_map.get(*source) else { continue };
+      let mut path: Vec<usize> = Vec::with_capacity(max_length);
+      let mut on_path = vec![false; self.currencies.len()];
+      on_path[source_index] = true;
+      self.enumerate_cycles_from(source_index, source_index, max_length, &adjacency, &mut path, &mut on_path, &mut |cycle| {
+        if seen.insert(Self::rotation_key(cycle)) {
+          cycles.push(cycle.to_vec());
+        }
+      });
+    }
+
+    cycles
+  }
+
+  /// Find Anchored Negative Cycle
+  /// Queue based relaxation (SPFA) from the sources which stops at the first negative cycle through one of them
+  /// A cycle missing every source is searched again with each of its edges banned in turn, so a shared edge never hides an anchored cycle
+  pub fn find_anchored_negative_cycle(&self, sources: &[&str]) -> Option<Vec<Edge>> {
+    let source_indices: Vec<usize> = sources.iter()
+      .filter_map(|source| self.currency_index_map.get(*source).copied())
+      .collect();
+    if source_indices.is_empty() { return None; }
+
+    let adjacency = self.adjacency();
+    let mut searched: HashSet<Vec<usize>> = HashSet::new();
+    let mut pending: Vec<Vec<usize>> = vec![vec![]];
+    let mut searches_left = self.edges.len() + 1;
+    while let Some(banned) = pending.pop() {
+
+      // Guard: Ensure the search stays bounded
+      if searches_left == 0 { return None; }
+      searches_left -= 1;
+
+      let mut is_banned = vec![false; self.edges.len()];
+      banned.iter().for_each(|&edge_index| is_banned[edge_index] = true);
+      let Some(mut cycle) = self.find_queued_cycle(&source_indices, &adjacency, &is_banned) else { continue };
+
+      // Anchored cycle, excluding two-edge reciprocations
+      let start = cycle.iter().position(|&edge_index| source_indices.contains(&self.edges[edge_index].from));
+      if let (Some(start), true) = (start, cycle.len() > 2) {
+        cycle.rotate_left(start);
+        return Some(self.to_named_cycle(&cycle));
+      }
+
+      // Branch on every edge of the cycle, the first edge is tried first
+      for &edge_index in cycle.iter().rev() {
+        let mut next: Vec<usize> = banned.iter().copied().chain([edge_index]).collect();
+        next.sort_unstable();
+        if searched.insert(next.clone()) {
+          pending.push(next);
+        }
+      }
+    }
+    None
+  }
+
+  /// Find Min Mean Cycle
+  /// Karp's algorithm for the cycle with the lowest mean edge weight (best per hop return)
+  /// Returns None unless that cycle is negative, i.e. profitable
+  pub fn find_min_mean_cycle(&self) -> Option<Vec<Edge>> {
+    let number_of_currencies = self.currencies.len();
+    if number_of_currencies == 0 { return None; }
+
+    // Walk distances of exactly k edges from an implicit source joined to every currency
+    let mut distance = vec![vec![f64::INFINITY; number_of_currencies]; number_of_currencies + 1];
+    let mut predecessor: Vec<Vec<Option<usize>>> = vec![vec![None; number_of_currencies]; number_of_currencies + 1];
+    distance[0].iter_mut().for_each(|d| *d = 0.0);
+
+    for k in 1..=number_of_currencies {
+      for (edge_index, edge) in self.edges.iter().enumerate() {
+        let candidate = distance[k - 1][edge.from] + edge.weight;
+        if candidate < distance[k][edge.to] {
+          distance[k][edge.to] = candidate;
+          predecessor[k][edge.to] = Some(edge_index);
+        }
+      }
+    }
+
+    // Min over v of max over k of (D[n][v] - D[k][v]) / (n - k)
+    let n = number_of_currencies;
+    let mut best: Option<(f64, usize)> = None;
+    for (v, &walk_distance) in distance[n].iter().enumerate() {
+      if walk_distance.is_infinite() { continue; }
+      let worst_mean = (0..n)
+        .filter(|&k| distance[k][v].is_finite())
+        .map(|k| (walk_distance - distance[k][v]) / (n - k) as f64)
+        .fold(f64::NEG_INFINITY, f64::max);
+      if best.is_none_or(|(mean, _)| worst_mean < mean) {
+        best = Some((worst_mean, v));
+      }
+    }
+
+    // Guard: Ensure the best cycle is profitable
+    let (min_mean, vertex) = best?;
+    if min_mean >= 0.0 { return None; }
+
+    // Any cycle on the n edge walk ending at vertex has the minimum mean
+    let mut walk: Vec<usize> = Vec::with_capacity(n);
+    let mut first_seen: HashMap<usize, usize> = HashMap::new();
+    let mut current = vertex;
+    for k in (1..=n).rev() {
+      if let Some(&position) = first_seen.get(&current) {
+        let mut cycle: Vec<usize> = walk[position..].to_vec();
+        cycle.reverse();
+        return Some(self.to_named_cycle(&cycle));
+      }
+      first_seen.insert(current, walk.len());
+      let edge_index = predecessor[k][current]?;
+      walk.push(edge_index);
+      current = self.edges[edge_index].from;
+    }
+
+    // The walk closes exactly on its last step
+    let position = *first_seen.get(&current)?;
+    let mut cycle: Vec<usize> = walk[position..].to_vec();
+    cycle.reverse();
+    Some(self.to_named_cycle(&cycle))
+  }
+
+  /// Find Best Route
+  /// Best conversion path from one currency into another within max_hops, by product of rates
+  pub fn find_best_route(&self, from: &str, to: &str, max_hops: usize) -> Option<Route> {
+    let &source = self.currency_index_map.get(from)?;
+    let &target = self.currency_index_map.get(to)?;
+
+    // Guard: Ensure a conversion is requested
+    if source == target { return None; }
+
+    let (distances, predecessors) = self.relax_hop_layers(source, max_hops);
+
+    // Best simple path over every hop count
+    let mut best: Option<(f64, Vec<usize>)> = None;
+    for (hops, distance) in distances.iter().enumerate().skip(1) {
+      if distance[target].is_infinite() { continue; }
+      if best.as_ref().is_some_and(|(weight, _)| *weight <= distance[target]) { continue; }
+      if let Some(path) = self.construct_layered_path(target, hops, &predecessors) {
+        best = Some((distance[target], path));
+      }
+    }
+
+    let (weight, path) = best?;
+    Some(Route {
+      edges: self.to_named_cycle(&path),
+      rate: 10f64.powf(-weight),
+    })
+  }
+
+  /// Cycle Weight
+  /// Sum of edge weights for a cycle of edge indices (negative means profitable)
+  pub fn cycle_weight(&self, cycle: &[usize]) -> f64 {
+    cycle.iter().map(|&edge_index| self.edges[edge_index].weight).sum()
+  }
+
+  /// To Directed Rates
+  /// Rebuilds the rates this graph was constructed from, i.e. after pruning, so it can be exported or reloaded
+  pub fn to_directed_rates(&self) -> Vec<DirectedRate> {
+    self.edges.iter().map(|edge| DirectedRate {
+      from: self.currencies[edge.from].clone(),
+      to: self.currencies[edge.to].clone(),
+      rate: f64::powf(10.0, -edge.weight),
+      symbol: self.symbols[edge.symbol].clone(),
+      direction: edge.direction,
+    }).collect()
+  }
+
+  /// To Named Edge
+  /// Maps an indexed edge back to its named assets
+  pub fn to_named_edge(&self, edge_index: usize) -> Edge {
+    let edge = &self.edges[edge_index];
+    Edge {
+      from: self.currencies[edge.from].clone(),
+      to: self.currencies[edge.to].clone(),
+      weight: edge.weight,
+      symbol: self.symbols[edge.symbol].clone(),
+      direction: edge.direction,
+    }
+  }
+
+  /// To Named Cycle
+  /// Maps a cycle of edge indices back to named edges
+  pub fn to_named_cycle(&self, cycle: &[usize]) -> Vec<Edge> {
+    cycle.iter().map(|&edge_index| self.to_named_edge(edge_index)).collect()
+  }
+
+  /// Intern
+  /// Retrieves or assigns the index for a given currency or symbol str
+  fn intern(name: &str, names: &mut Vec<String>, index_map: &mut HashMap<String, usize>) -> usize {
+    if let Some(&index) = index_map.get(name) {
+      return index;
+    }
+    let index = names.len();
+    names.push(name.to_string());
+    index_map.insert(name.to_string(), index);
+    index
+  }
+
+  /// Relax Edges
+  /// Runs |V| relaxation passes from source, storing the predecessor edge index of each vertex
+  fn relax_edges(&self, source: usize, is_early_exit: bool) -> (Vec<f64>, Vec<Option<usize>>) {
+    let number_of_currencies = self.currencies.len();
+    let mut distance = vec![f64::INFINITY; number_of_currencies];
+    let mut predecessor: Vec<Option<usize>> = vec![None; number_of_currencies];
+
+    if number_of_currencies == 0 {
+      return (distance, predecessor);
+    }
+
+    distance[source] = 0.0;
 
-    // Relax edges
     for _ in 0..number_of_currencies {
-        for edge in &self.edges {
-            let u = self.get_currency_index(&edge.from);
-            let v = self.get_currency_index(&edge.to);
-            if distance[u] + edge.weight < distance[v] {
-                distance[v] = distance[u] + edge.weight;
-                predecessor[v] = Some(edge.clone());
-            }
+      let mut update = false; // Flag to check if any update happens in this iteration
+
+      for (edge_index, edge) in self.edges.iter().enumerate() {
+        if distance[edge.from] + edge.weight < distance[edge.to] {
+          distance[edge.to] = distance[edge.from] + edge.weight;
+          predecessor[edge.to] = Some(edge_index);
+          update = true; // Update flag when a change occurs
         }
+      }
+
+      // Early termination if no update in this iteration
+      if is_early_exit && !update {
+        break;
+      }
     }
 
-    // Check for negative cycles
+    (distance, predecessor)
+  }
+
+  /// Find Bounded Cycles From
+  /// A cycle closes whenever the best path of k edges to v plus the edge v -> source is negative
+  fn find_bounded_cycles_from(&self, source: usize, max_length: usize) -> Vec<Vec<usize>> {
     let mut cycles = Vec::new();
-    for edge in &self.edges {
-        let u = self.get_currency_index(&edge.from);
-        let v = self.get_currency_index(&edge.to);
+    let (distances, predecessors) = self.relax_hop_layers(source, max_length.saturating_sub(1));
+
+    // Guard: Ensure cycles are longer than a two-edge reciprocation
+    for (hops, distance) in distances.iter().enumerate().skip(2) {
 
-        // Skip the edge if it was part of a previously found cycle
-        if visited_edges.contains(&(u, v)) {
-            continue;
+      // Close cycles back to source
+      for (edge_index, edge) in self.edges.iter().enumerate() {
+        if edge.to != source || edge.from == source { continue; }
+        if distance[edge.from] + edge.weight >= 0.0 { continue; }
+        if let Some(mut cycle) = self.construct_layered_path(edge.from, hops, &predecessors) {
+          cycle.push(edge_index);
+          cycles.push(cycle);
         }
+      }
+    }
+
+    cycles
+  }
+
+  /// Relax Hop Layers
+  /// Hop-layered relaxation from source where layer k holds the best path of exactly k edges
+  /// The source is only ever left at the first hop and never passed through
+  fn relax_hop_layers(&self, source: usize, max_hops: usize) -> (Vec<Vec<f64>>, Vec<Vec<Option<usize>>>) {
+    let number_of_currencies = self.currencies.len();
+    let mut distances: Vec<Vec<f64>> = vec![vec![f64::INFINITY; number_of_currencies]];
+    let mut predecessors: Vec<Vec<Option<usize>>> = vec![vec![None; number_of_currencies]];
+
+    distances[0][source] = 0.0;
 
-        if distance[u] + edge.weight < distance[v] {
-            let cycle = self.construct_cycle(v, &predecessor);
-            if !cycle.is_empty() {
-                // Mark all edges in the cycle as visited
-                for cycle_edge in &cycle {
-                    let from_index = self.get_currency_index(&cycle_edge.from);
-                    let to_index = self.get_currency_index(&cycle_edge.to);
-                    visited_edges.insert((from_index, to_index));
-                }
+    for hops in 1..=max_hops {
+      let distance = &distances[hops - 1];
+      let mut next_distance = vec![f64::INFINITY; number_of_currencies];
+      let mut next_predecessor: Vec<Option<usize>> = vec![None; number_of_currencies];
 
-                if !cycles.contains(&cycle) {
-                  cycles.push(cycle);
-                }
-            }
+      for (edge_index, edge) in self.edges.iter().enumerate() {
+        if edge.to == source || (edge.from == source && hops > 1) { continue; }
+
+        if distance[edge.from] + edge.weight < next_distance[edge.to] {
+          next_distance[edge.to] = distance[edge.from] + edge.weight;
+          next_predecessor[edge.to] = Some(edge_index);
         }
+      }
+
+      distances.push(next_distance);
+      predecessors.push(next_predecessor);
     }
 
-    cycles
+    (distances, predecessors)
   }
 
-  /// Get Currency Index
-  /// Retrieves the index for a given currency str
-  fn get_currency_index(&self, currency: &str) -> usize {
-    let i = self.currency_index_map.get(currency).copied().expect("Missing currency index");
-    i
+  /// Construct Layered Path
+  /// Walks layered predecessors back to the source, rejecting paths that revisit a currency
+  fn construct_layered_path(&self, end: usize, hops: usize, predecessors: &[Vec<Option<usize>>]) -> Option<Vec<usize>> {
+    let mut path = Vec::with_capacity(hops + 1);
+    let mut visited = HashSet::new();
+    let mut current = end;
+
+    for layer in (1..=hops).rev() {
+      if !visited.insert(current) { return None; }
+      let edge_index = predecessors[layer][current]?;
+      path.push(edge_index);
+      current = self.edges[edge_index].from;
+    }
+
+    path.reverse();
+    Some(path)
+  }
+
+  /// Enumerate Cycles From
+  /// Depth first walk of simple paths from source, reporting each one that closes back to source
+  #[allow(clippy::too_many_arguments)]
+  fn enumerate_cycles_from(
+    &self,
+    source: usize,
+    current: usize,
+    max_length: usize,
+    adjacency: &[Vec<usize>],
+    path: &mut Vec<usize>,
+    on_path: &mut [bool],
+    on_cycle: &mut dyn FnMut(&[usize])
+  ) {
+    for &edge_index in &adjacency[current] {
+      let next = self.edges[edge_index].to;
+      path.push(edge_index);
+
+      if next == source {
+        // Guard: Ensure cycles are longer than a two-edge reciprocation
+        if path.len() > 2 { on_cycle(path); }
+      } else if !on_path[next] && path.len() < max_length {
+        on_path[next] = true;
+        self.enumerate_cycles_from(source, next, max_length, adjacency, path, on_path, on_cycle);
+        on_path[next] = false;
+      }
+
+      path.pop();
+    }
+  }
+
+  /// Find Queued Cycle
+  /// Runs SPFA and walks back to the root after every relaxation, returning the first predecessor cycle as edge indices
+  /// None once every distance settles, meaning no reachable negative cycle remains
+  fn find_queued_cycle(&self, sources: &[usize], adjacency: &[Vec<usize>], is_banned: &[bool]) -> Option<Vec<usize>> {
+    let mut distance = vec![f64::INFINITY; self.currencies.len()];
+    let mut predecessor: Vec<Option<usize>> = vec![None; self.currencies.len()];
+    let mut is_queued = vec![false; self.currencies.len()];
+    let mut queue: VecDeque<usize> = VecDeque::new();
+    for &source in sources {
+      distance[source] = 0.0;
+      is_queued[source] = true;
+      queue.push_back(source);
+    }
+
+    while let Some(u) = queue.pop_front() {
+      is_queued[u] = false;
+      for &edge_index in &adjacency[u] {
+        if is_banned[edge_index] { continue; }
+        let edge = &self.edges[edge_index];
+        if distance[u] + edge.weight >= distance[edge.to] { continue; }
+
+        // Predecessor graph stays acyclic until the relaxed vertex is an ancestor of u
+        let mut current = u;
+        while current != edge.to {
+          match predecessor[current] {
+            Some(parent_edge) => current = self.edges[parent_edge].from,
+            None => break
+          }
+        }
+        predecessor[edge.to] = Some(edge_index);
+        if current == edge.to {
+          let mut cycle = vec![edge_index];
+          let mut current = u;
+          while current != edge.to {
+            let parent_edge = predecessor[current].unwrap();
+            cycle.push(parent_edge);
+            current = self.edges[parent_edge].from;
+          }
+          cycle.reverse();
+          return Some(cycle);
+        }
+
+        distance[edge.to] = distance[u] + edge.weight;
+        if !is_queued[edge.to] {
+          is_queued[edge.to] = true;
+          queue.push_back(edge.to);
+        }
+      }
+    }
+    None
+  }
+
+  /// Adjacency
+  /// Outgoing edge indices for each currency
+  fn adjacency(&self) -> Vec<Vec<usize>> {
+    let mut adjacency = vec![Vec::new(); self.currencies.len()];
+    for (edge_index, edge) in self.edges.iter().enumerate() {
+      adjacency[edge.from].push(edge_index);
+    }
+    adjacency
+  }
+
+  /// Rotation Key
+  /// Rotates a cycle of edge indices to start at its smallest edge index
+  fn rotation_key(cycle: &[usize]) -> Vec<usize> {
+    let start = cycle.iter().enumerate().min_by_key(|(_, &edge_index)| edge_index).map(|(i, _)| i).unwrap_or(0);
+    cycle[start..].iter().chain(cycle[..start].iter()).copied().collect()
   }
 
   /// Construct Cycle
-  /// Provides ordering and information for the cycle in question
-  fn construct_cycle(&self, start: usize, predecessor: &Vec<Option<Edge>>) -> Vec<Edge> {
+  /// Provides ordering and information for the cycle in question as edge indices
+  fn construct_cycle(&self, start: usize, predecessor: &[Option<usize>]) -> Vec<usize> {
     let mut cycle = Vec::new();
-    let mut visited = std::collections::HashSet::new();
+    let mut visited = HashSet::new();
     let mut current = start;
 
     // Find the actual start of the cycle
     let mut cycle_start = None;
-    while let Some(edge) = &predecessor[current] {
+    while let Some(edge_index) = predecessor[current] {
       if visited.contains(&current) {
         cycle_start = Some(current);
         break;
       }
       visited.insert(current);
-      current = self.get_currency_index(&edge.from);
+      current = self.edges[edge_index].from;
     }
 
     // Reconstruct the cycle from the start
     if let Some(cycle_start_vertex) = cycle_start {
-      cycle.clear();
       visited.clear();
       current = cycle_start_vertex;
 
-      loop {
-        if let Some(edge) = &predecessor[current] {
-          if visited.contains(&current) {
-            break;
-          }
-          visited.insert(current);
-          cycle.push(edge.clone());
-          current = self.get_currency_index(&edge.from);
-        } else {
+      while let Some(edge_index) = predecessor[current] {
+        if visited.contains(&current) {
           break;
         }
+        visited.insert(current);
+        cycle.push(edge_index);
+        current = self.edges[edge_index].from;
       }
     }
 
@@ -191,20 +800,31 @@ impl BellmanFord {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::exchanges::snapshot::BinanceSnapshot;
 
-  fn get_test_rates_fx() -> Vec<(String, String, f64)> {
+  fn get_test_rate(from: &str, to: &str, rate: f64) -> DirectedRate {
+    DirectedRate {
+      from: from.to_string(),
+      to: to.to_string(),
+      rate,
+      symbol: format!("{}{}", from, to),
+      direction: Direction::Forward
+    }
+  }
+
+  fn get_test_rates_fx() -> Vec<DirectedRate> {
     vec![
-      ("USD".to_string(), "EUR".to_string(), 0.9),
-      ("EUR".to_string(), "USD".to_string(), 1.21),
-      ("USD".to_string(), "GBP".to_string(), 0.75),
-      ("GBP".to_string(), "USD".to_string(), 1.33),
-      ("GBP".to_string(), "EUR".to_string(), 1.197),
+      get_test_rate("USD", "EUR", 0.9),
+      get_test_rate("EUR", "USD", 1.21),
+      get_test_rate("USD", "GBP", 0.75),
+      get_test_rate("GBP", "USD", 1.33),
+      get_test_rate("GBP", "EUR", 1.197),
     ]
   }
 
   #[tokio::test]
   async fn it_detects_arbitrage_negative_cycle() {
-    let test_exchange_rates: Vec<(String, String, f64)> = get_test_rates_fx();
+    let test_exchange_rates: Vec<DirectedRate> = get_test_rates_fx();
     let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
     match bf.find_negative_cycle() {
       Some(cycle) => {
@@ -225,7 +845,328 @@ mod tests {
     let test_exchange_rates = get_test_rates_fx();
     let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
     let cycles = bf.find_all_negative_cycles();
-    assert!(cycles.len() > 0);
-    assert!(cycles[0].len() > 0);
+    assert!(!cycles.is_empty());
+    assert!(!cycles[0].is_empty());
+  }
+
+  #[tokio::test]
+  async fn it_detects_bounded_cycles_rotated_to_source() {
+    let test_exchange_rates = get_test_rates_fx();
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    let cycles = bf.find_bounded_negative_cycles(&["EUR"], 3);
+    assert!(!cycles.is_empty());
+    for cycle in &cycles {
+      assert!(cycle.len() <= 3);
+      assert_eq!(cycle[0].from, "EUR");
+      assert_eq!(cycle.last().unwrap().to, "EUR");
+      assert!(cycle.iter().map(|edge| edge.weight).sum::<f64>() < 0.0);
+    }
+
+    // Same loop found from two sources is only returned once
+    let cycles_multi = bf.find_bounded_negative_cycles(&["USD", "EUR"], 3);
+    assert_eq!(cycles_multi.len(), cycles.len());
+    assert_eq!(cycles_multi[0][0].from, "USD");
+
+    // Two hops only allows reciprocations
+    assert!(bf.find_bounded_negative_cycles(&["USD"], 2).is_empty());
+  }
+
+  #[tokio::test]
+  async fn it_enumerates_and_ranks_bounded_cycles() {
+    let test_exchange_rates: Vec<DirectedRate> = vec![
+      get_test_rate("A", "B", 1.0),
+      get_test_rate("B", "C", 1.0),
+      get_test_rate("C", "A", 1.02),
+      get_test_rate("A", "D", 1.0),
+      get_test_rate("D", "C", 1.01),
+      get_test_rate("B", "D", 1.005),
+    ];
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    // Rotations found from both sources are only listed once
+    let cycles = bf.enumerate_cycles(&["A", "C"], 4, 10);
+    assert_eq!(cycles.len(), 3);
+    let path: Vec<&str> = cycles[0].iter().map(|edge| edge.from.as_str()).collect();
+    assert_eq!(path, vec!["A", "B", "D", "C"]);
+    assert_eq!(cycles[1].len(), 3);
+    assert_eq!(cycles[2].len(), 3);
+
+    // Ranking and bounds
+    assert_eq!(bf.enumerate_cycles(&["A"], 4, 2).len(), 2);
+    assert_eq!(bf.enumerate_cycles(&["A"], 3, 10).len(), 2);
+  }
+
+  #[tokio::test]
+  async fn it_ranks_short_strong_cycle_above_long_marginal_cycle() {
+    let test_exchange_rates: Vec<DirectedRate> = vec![
+      // Long marginal loop: A -> B -> C -> D -> E -> A at 1.025 overall
+      get_test_rate("A", "B", 1.005),
+      get_test_rate("B", "C", 1.005),
+      get_test_rate("C", "D", 1.005),
+      get_test_rate("D", "E", 1.005),
+      get_test_rate("E", "A", 1.005),
+      // Short strong loop: A -> F -> G -> A at 1.0203 overall
+      get_test_rate("A", "F", 1.01),
+      get_test_rate("F", "G", 1.0),
+      get_test_rate("G", "A", 1.0102),
+    ];
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    let cycle = bf.find_min_mean_cycle().unwrap();
+    let mut path: Vec<&str> = cycle.iter().map(|edge| edge.from.as_str()).collect();
+    path.sort();
+    assert_eq!(path, vec!["A", "F", "G"]);
+
+    // Per hop return beats every cycle the Bellman Ford detectors find on the same graph
+    let mean = |cycle: &Vec<Edge>| cycle.iter().map(|edge| edge.weight).sum::<f64>() / cycle.len() as f64;
+    for other in bf.enumerate_cycles(&["A"], 5, 10) {
+      assert!(mean(&cycle) <= mean(&other) + 1e-12);
+    }
+  }
+
+  #[tokio::test]
+  async fn it_returns_no_min_mean_cycle_without_arbitrage() {
+    let test_exchange_rates: Vec<DirectedRate> = vec![
+      get_test_rate("USD", "EUR", 0.9),
+      get_test_rate("EUR", "USD", 1.1),
+      get_test_rate("EUR", "GBP", 0.85),
+      get_test_rate("GBP", "USD", 1.3),
+    ];
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+    assert!(bf.find_min_mean_cycle().is_none());
+
+    let bf: BellmanFord = BellmanFord::new(&get_test_rates_fx());
+    assert!(bf.find_min_mean_cycle().is_some());
+  }
+
+  #[tokio::test]
+  async fn it_finds_best_conversion_route_within_hop_limit() {
+    let test_exchange_rates: Vec<DirectedRate> = vec![
+      get_test_rate("LINK", "USDT", 14.0),
+      get_test_rate("LINK", "BTC", 0.00036),
+      get_test_rate("BTC", "USDT", 40000.0),
+      get_test_rate("LINK", "ETH", 0.0071),
+      get_test_rate("ETH", "BTC", 0.051),
+    ];
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    // LINK -> BTC -> USDT beats the direct pair
+    let route = bf.find_best_route("LINK", "USDT", 2).unwrap();
+    assert_eq!(route.edges.len(), 2);
+    assert_eq!(route.edges[0].symbol, "LINKBTC");
+    assert_eq!(route.edges[1].symbol, "BTCUSDT");
+    assert!((route.rate - 14.4).abs() < 1e-9);
+
+    // Three hops allows LINK -> ETH -> BTC -> USDT
+    let route = bf.find_best_route("LINK", "USDT", 3).unwrap();
+    assert_eq!(route.edges.len(), 3);
+    assert!((route.rate - 0.0071 * 0.051 * 40000.0).abs() < 1e-9);
+
+    // Single hop falls back to the direct pair
+    let route = bf.find_best_route("LINK", "USDT", 1).unwrap();
+    assert_eq!(route.edges.len(), 1);
+    assert!((route.rate - 14.0).abs() < 1e-9);
+
+    assert!(bf.find_best_route("USDT", "LINK", 3).is_none());
+  }
+
+  #[tokio::test]
+  async fn it_prunes_dead_ends_and_splits_components() {
+    let mut test_exchange_rates = get_test_rates_fx();
+    test_exchange_rates.extend(vec![
+      // Dead end: only ever quoted against USD
+      get_test_rate("XRP", "USD", 0.5),
+      get_test_rate("USD", "XRP", 1.9),
+      // Separate loop not reachable from USD
+      get_test_rate("A", "B", 1.0),
+      get_test_rate("B", "C", 1.0),
+      get_test_rate("C", "A", 1.05),
+    ]);
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    let pruned = bf.prune_dead_ends();
+    assert!(!pruned.currency_index_map.contains_key("XRP"));
+    assert_eq!(pruned.currencies.len(), 6);
+
+    let components = bf.components();
+    assert_eq!(components.len(), 2);
+
+    // Every cycle of the full graph is still found, plus the unreachable loop
+    let cycles = bf.find_all_negative_cycles_by_component();
+    for cycle in bf.find_all_negative_cycles() {
+      assert!(cycles.iter().any(|found| found.len() == cycle.len() && cycle.iter().all(|edge| found.contains(edge))));
+    }
+    assert!(cycles.iter().any(|cycle| cycle.iter().any(|edge| edge.from == "A")));
+  }
+
+  #[tokio::test]
+  async fn it_merges_parallel_multi_source_cycles() {
+    let mut test_exchange_rates = get_test_rates_fx();
+    test_exchange_rates.extend(vec![
+      get_test_rate("A", "B", 1.0),
+      get_test_rate("B", "C", 1.0),
+      get_test_rate("C", "A", 1.05),
+    ]);
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    // Each source reaches its own loop, the shared loop is only listed once
+    let cycles = bf.find_all_negative_cycles_parallel(&["USD", "EUR", "A"]);
+    assert_eq!(cycles.len(), 2);
+    assert!(cycles.iter().any(|cycle| cycle.iter().any(|edge| edge.from == "A")));
+    assert!(cycles.iter().any(|cycle| cycle.iter().any(|edge| edge.from == "USD")));
+  }
+
+  /// Synthetic universe shaped like Binance spot: many alts quoted against a few majors
+  fn get_bench_rates(number_of_assets: usize) -> Vec<DirectedRate> {
+    let quotes = ["USDT", "BTC", "ETH", "BNB", "FDUSD"];
+    let mut seed: u64 = 42;
+    let mut next = || {
+      seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+      (seed >> 33) as f64 / (1u64 << 31) as f64
+    };
+    let mut rates = Vec::new();
+    for i in 0..number_of_assets {
+      let base = format!("ALT{}", i);
+      let usd_price = 0.01 + next() * 100.0;
+      for (q, quote) in quotes.iter().enumerate() {
+        if q > 0 && next() > 0.4 { continue; }
+        let quote_usd = [1.0, 40000.0, 2000.0, 300.0, 1.0][q];
+        let mid = usd_price / quote_usd * (1.0 + (next() - 0.5) * 0.004);
+        rates.push(DirectedRate { from: base.clone(), to: quote.to_string(), rate: mid * 0.999, symbol: format!("{}{}", base, quote), direction: Direction::Forward });
+        rates.push(DirectedRate { from: quote.to_string(), to: base.clone(), rate: 0.999 / mid, symbol: format!("{}{}", base, quote), direction: Direction::Reverse });
+      }
+    }
+    rates
+  }
+
+  #[tokio::test]
+  #[ignore] // cargo test --release it_benchmarks_parallel_detection -- --ignored --nocapture
+  async fn it_benchmarks_parallel_detection() {
+
+    // A snapshot recorded with Binance::save_snapshot when BENCH_SNAPSHOT points at one, else a 400 asset universe
+    let exchange_rates = match std::env::var("BENCH_SNAPSHOT") {
+      Ok(path) => BinanceSnapshot::load(&path).unwrap().exchange_rates,
+      Err(_) => get_bench_rates(400)
+    };
+    let bf: BellmanFord = BellmanFord::new(&exchange_rates);
+    let sources: Vec<&str> = ["USDT", "BTC", "ETH", "BNB"].into_iter().filter(|source| bf.currency_index_map.contains_key(*source)).collect();
+    assert!(!sources.is_empty());
+
+    let start = std::time::Instant::now();
+    let single: Vec<Vec<Edge>> = sources.iter()
+      .flat_map(|source| bf.find_all_negative_cycles_from(bf.currency_index_map[*source]))
+      .map(|cycle| bf.to_named_cycle(&cycle))
+      .collect();
+    let single_elapsed = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let parallel = bf.find_all_negative_cycles_parallel(&sources);
+    let parallel_elapsed = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let by_component = bf.find_all_negative_cycles_by_component();
+    let component_elapsed = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let anchored = bf.find_anchored_negative_cycle(&sources);
+    let anchored_elapsed = start.elapsed();
+
+    println!("edges: {}, currencies: {}", bf.edges.len(), bf.currencies.len());
+    println!("single source x {}: {:?} ({} cycles)", sources.len(), single_elapsed, single.len());
+    println!("parallel ({} sources): {:?} ({} cycles)", sources.len(), parallel_elapsed, parallel.len());
+    println!("by component:    {:?} ({} cycles)", component_elapsed, by_component.len());
+    println!("spfa first anchored: {:?} (found: {})", anchored_elapsed, anchored.is_some());
+
+    // Every cycle a single run from an anchor source finds is in the merged parallel result
+    assert!(!single.is_empty());
+    for cycle in &single {
+      assert!(parallel.iter().any(|found| found.len() == cycle.len() && cycle.iter().all(|edge| found.contains(edge))));
+    }
+  }
+
+  #[tokio::test]
+  async fn it_stops_at_first_cycle_through_a_source() {
+    let mut test_exchange_rates = get_test_rates_fx();
+    test_exchange_rates.extend(vec![
+      get_test_rate("USD", "A", 1.0),
+      get_test_rate("A", "B", 1.0),
+      get_test_rate("B", "C", 1.0),
+      get_test_rate("C", "A", 1.5),
+    ]);
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    // Stronger loop A -> B -> C is reachable from USD but is skipped as it never returns to USD
+    let cycle = bf.find_anchored_negative_cycle(&["USD"]).unwrap();
+    assert_eq!(cycle[0].from, "USD");
+    assert_eq!(cycle.last().unwrap().to, "USD");
+    assert!(cycle.iter().all(|edge| !["A", "B", "C"].contains(&edge.from.as_str())));
+
+    // Only the unanchored loop remains
+    let test_exchange_rates: Vec<DirectedRate> = test_exchange_rates.into_iter().filter(|rate| rate.to != "EUR").collect();
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+    assert!(bf.find_anchored_negative_cycle(&["USD"]).is_none());
+    assert!(bf.find_anchored_negative_cycle(&["A"]).is_some());
+    assert!(bf.find_anchored_negative_cycle(&["XYZ"]).is_none());
+  }
+
+  #[tokio::test]
+  async fn it_finds_an_anchored_cycle_sharing_an_edge_with_an_unanchored_one() {
+    let test_exchange_rates = vec![
+      get_test_rate("USD", "A", 1.0),
+      get_test_rate("A", "B", 1.0),
+      get_test_rate("B", "C", 1.0),
+      get_test_rate("C", "A", 1.5),
+      get_test_rate("B", "D", 1.0),
+      get_test_rate("D", "USD", 1.02),
+    ];
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+
+    // A -> B -> C is found first and shares A -> B with the anchored loop
+    let cycle = bf.find_anchored_negative_cycle(&["USD"]).unwrap();
+    let path: Vec<&str> = cycle.iter().map(|edge| edge.from.as_str()).collect();
+    assert_eq!(path, vec!["USD", "A", "B", "D"]);
+  }
+
+  #[tokio::test]
+  async fn it_keys_rotations_of_a_cycle_identically() {
+    let test_exchange_rates = get_test_rates_fx();
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+    let cycle = bf.find_negative_cycle().unwrap();
+
+    let mut rotated = cycle.clone();
+    rotated.rotate_left(1);
+    rotated[0].weight += 0.001;
+    let key = CycleKey::from_cycle(&cycle);
+    assert_eq!(key, CycleKey::from_cycle(&rotated));
+    assert_eq!(key.assets[0], "EUR");
+    assert_eq!(key.to_string(), format!("{}|{}", key.assets.join(">"), key.symbols.join(">")));
+
+    // Same assets through a different market is a different opportunity
+    let mut other_market = cycle.clone();
+    other_market[0].symbol = "OTHER".to_string();
+    assert_ne!(key, CycleKey::from_cycle(&other_market));
+
+    let deduped = BellmanFord::dedup_cycles(vec![cycle, rotated, other_market]);
+    assert_eq!(deduped.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn it_maps_indexed_cycle_back_to_named_assets() {
+    let test_exchange_rates = get_test_rates_fx();
+    let bf: BellmanFord = BellmanFord::new(&test_exchange_rates);
+    assert_eq!(bf.currencies.len(), 3);
+    assert_eq!(bf.edges.len(), test_exchange_rates.len());
+
+    let cycle = bf.find_negative_cycle().unwrap();
+    for pair in cycle.windows(2) {
+      assert_eq!(pair[0].to, pair[1].from);
+    }
+    assert_eq!(cycle.last().unwrap().to, cycle[0].from);
+    for edge in &cycle {
+      let from_index = bf.currency_index_map[&edge.from];
+      assert_eq!(bf.currencies[from_index], edge.from);
+      assert_eq!(edge.symbol, format!("{}{}", edge.from, edge.to));
+    }
   }
 }
@@ -1,11 +1,37 @@
-use super::models::Mode;
+use super::models::{BinanceEnvironment, GraphMode, Mode};
 
 /// Searcher: Trades entire pool of assets
 /// Listener: Listens to and trades specific pool of assets
 pub const MODE: Mode = Mode::Searcher(true, false); // bool = is save results, bool = is trade
+pub const IS_PAPER_TRADE: bool = false; // i.e. true to simulate trades against live books on a virtual balance sheet
+pub const PAPER_BALANCES: [(&str, f64); 2] = [("USDT", 1000.0), ("BTC", 0.02)]; // starting virtual balances
+
+/// Production, Testnet or Custom { rest_url, ws_url } i.e. a local mock server
+pub const BINANCE_ENVIRONMENT: BinanceEnvironment = BinanceEnvironment::Production;
 
 pub const ASSET_HOLDINGS: [&str; 2] = ["USDT", "BTC"];
 pub const FIAT_EXCLUSION: [&str; 13] = ["ARS", "BIDR", "BRL", "EUR", "GBP", "IDRT", "NGN", "PLN", "RON", "RUB", "TRY", "UAH", "ZAR"];
 pub const USD_BUDGET: f64 = 25.0; // USD equivalent in each asset holding
 pub const MAX_CYCLE_LENGTH: usize = 5;
+pub const TOP_N_CYCLES: usize = 10; // cycles kept when enumerating every cycle
+pub const GRAPH_MODE: GraphMode = GraphMode::TopOfBook;
+pub const VWAP_NOTIONAL_USD: f64 = USD_BUDGET; // USD equivalent walked through each book in Vwap mode
+pub const ORDERBOOK_CACHE_MS: u64 = 1000; // age after which a cached orderbook is refetched
+pub const HTTP_TIMEOUT_MS: u64 = 5000; // whole request timeout
+pub const HTTP_CONNECT_TIMEOUT_MS: u64 = 2000;
+pub const HTTP_POOL_IDLE_TIMEOUT_MS: u64 = 90000; // idle pooled connections kept open for reuse
+pub const HTTP_MAX_RETRIES: u32 = 2; // GET retries on timeouts, connection errors and 5xx, orders are never retried
+pub const HTTP_RETRY_BACKOFF_MS: u64 = 100; // doubled after every retry
+pub const RATE_LIMIT_HEADROOM: f64 = 0.9; // i.e. 0.9 holds calls back once 90% of a Binance limit is used
+pub const RATE_LIMIT_MAX_WAIT_MS: u64 = 2000; // longest a call is queued for a limit window before it is rejected
+pub const ORDER_MAX_RETRIES: u32 = 1; // re-signed resends of a leg Binance rejected with a retryable error, i.e. -1021
+#[allow(dead_code)]
+pub const SNAPSHOT_PATH: &str = "fixtures/binance_snapshot.json"; // recorded exchange state loaded by BinanceSnapshot
+pub const GRAPH_EXPORT_DIR: Option<&str> = None; // i.e. Some("graph_exports") to write the scanned graph every iteration
 pub const MIN_ARB_THRESH: f64 = 1.015; // i.e. 1.015 for 1.5%
+pub const CYCLE_COOLDOWN_MS: u64 = 5000; // time before the same cycle is acted on again
+
+pub const TAKER_FEE: f64 = 0.001; // i.e. 0.001 for 0.1%
+pub const TAKER_FEE_OVERRIDES: [(&str, f64); 0] = []; // i.e. ("BTCFDUSD", 0.0) for per symbol fees
+pub const IS_BNB_FEE_DISCOUNT: bool = false; // pay fees in BNB
+pub const BNB_FEE_DISCOUNT: f64 = 0.25; // i.e. 0.25 for 25% off fees paid in BNB
@@ -1,86 +1,238 @@
-use crate::bellmanford::{BellmanFord, Edge};
-use crate::constants::FIAT_EXCLUSION;
-use crate::models::{Direction, SmartError, SymbolInfo};
+use crate::constants::{
+  FIAT_EXCLUSION, GRAPH_MODE, HTTP_CONNECT_TIMEOUT_MS, HTTP_MAX_RETRIES, HTTP_POOL_IDLE_TIMEOUT_MS, HTTP_RETRY_BACKOFF_MS,
+  HTTP_TIMEOUT_MS, ORDERBOOK_CACHE_MS, VWAP_NOTIONAL_USD
+};
+use crate::models::{BinanceConfig, BinanceError, DirectedRate, Direction, ExchangeRate, FeeSchedule, GraphMode, OrderBook, SmartError, SymbolInfo};
 use crate::traits::{ApiCalls, BellmanFordEx, ExchangeData};
 use crate::helpers;
+use super::binance_models::{
+  parse_levels, parse_response, AccountResponse, BookTickerResponse, DepthResponse, ErrorResponse, ExchangeInfoResponse, OrderResponse,
+  TickerPriceResponse
+};
+use super::rate_limit::{request_weight, RateLimiter};
+use super::snapshot::BinanceSnapshot;
 
 use async_trait::async_trait;
+use rust_decimal::Decimal;
+use futures::future::join_all;
 
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::collections::HashMap;
-use dotenv::dotenv;
-use std::env;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct Binance {
+  pub config: BinanceConfig,
+  pub client: reqwest::Client,
+  pub limiter: Arc<RateLimiter>,
   pub symbols: HashMap<String, SymbolInfo>,
   pub prices: HashMap<String, f64>,
-  pub exchange_rates: Vec<(String, String, f64)>,
+  pub book_tickers: HashMap<String, ExchangeRate>,
+  pub exchange_rates: Vec<DirectedRate>,
+  pub fees: FeeSchedule,
+  pub orderbooks: HashMap<String, OrderBook>,
+}
+
+impl Binance {
+  /// With Config
+  /// Initializes exchange data from the environment (production, testnet or a mock server) in config
+  pub async fn with_config(config: BinanceConfig) -> Self {
+    let mut exchange = Self::unloaded(config);
+    exchange.refresh().await.expect("Failed to fetch Binance exchange data");
+    exchange
+  }
+
+  /// Unloaded
+  /// Exchange with its http client and rate limiter but no market data yet, refresh loads it
+  pub fn unloaded(config: BinanceConfig) -> Self {
+    Self {
+      config,
+      client: Self::http_client(),
+      limiter: Arc::new(RateLimiter::default()),
+      symbols: HashMap::new(),
+      prices: HashMap::new(),
+      book_tickers: HashMap::new(),
+      exchange_rates: vec![],
+      fees: FeeSchedule::from_constants(),
+      orderbooks: HashMap::new(),
+    }
+  }
+
+  /// Refresh
+  /// Re-fetches symbols, prices and book tickers in place so the http client and rate limiter live across scans
+  /// Nothing is replaced unless every fetch succeeds
+  pub async fn refresh(&mut self) -> Result<(), SmartError> {
+    let symbols = self.fetch_symbols().await?;
+    let prices = self.fetch_prices().await?;
+    let book_tickers = self.fetch_book_tickers(&symbols).await?;
+    self.exchange_rates = helpers::create_exchange_rates(&book_tickers, &self.fees);
+    self.symbols = symbols;
+    self.prices = prices;
+    self.book_tickers = book_tickers;
+    Ok(())
+  }
+
+  /// Http Client
+  /// Pooled keep-alive client with timeouts, cloned exchanges share its connections
+  pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+      .timeout(Duration::from_millis(HTTP_TIMEOUT_MS))
+      .connect_timeout(Duration::from_millis(HTTP_CONNECT_TIMEOUT_MS))
+      .pool_idle_timeout(Duration::from_millis(HTTP_POOL_IDLE_TIMEOUT_MS))
+      .tcp_keepalive(Duration::from_millis(HTTP_POOL_IDLE_TIMEOUT_MS))
+      .tcp_nodelay(true)
+      .build()
+      .expect("Failed to build Binance http client")
+  }
+
+  /// Send
+  /// Sends a request once the rate limiter has room for its weight, then syncs the limiter from the response
+  /// Rejected requests come back as SmartError::Binance with the code and msg Binance sent
+  async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, SmartError> {
+    let request = request.build()?;
+    let path = request.url().path().to_string();
+    self.limiter.acquire(request_weight(&path), path == "/api/v3/order").await?;
+
+    let response = self.client.execute(request).await?;
+    self.limiter.update(response.status(), response.headers());
+
+    // Guard: Ensure request was accepted
+    if response.status().is_success() { return Ok(response); }
+
+    let status = response.status().as_u16();
+    let body = response.text().await?;
+    let error = match serde_json::from_str::<ErrorResponse>(&body) {
+      Ok(error) => BinanceError { path, status, code: error.code, msg: error.msg },
+      Err(_) => BinanceError { path, status, code: 0, msg: body.chars().take(500).collect() }
+    };
+    Err(SmartError::Binance(error))
+  }
+
+  /// Send With Retry
+  /// Sends an idempotent request, retrying timeouts, connection errors, 5xx and retryable Binance errors with exponential backoff
+  /// Every attempt is built afresh so signed requests carry a new timestamp and signature
+  async fn send_with_retry<F>(&self, build: F) -> Result<reqwest::Response, SmartError>
+    where F: Fn() -> reqwest::RequestBuilder
+  {
+    let mut attempt = 0;
+    loop {
+      let result = self.send(build()).await;
+      let is_retryable = match &result {
+        Ok(_) => false,
+        Err(SmartError::Binance(error)) => error.kind().is_retryable(),
+        Err(SmartError::Reqwest(e)) => e.is_timeout() || e.is_connect(),
+        Err(_) => false
+      };
+      if !is_retryable || attempt >= HTTP_MAX_RETRIES {
+        return result;
+      }
+      tokio::time::sleep(Duration::from_millis(HTTP_RETRY_BACKOFF_MS << attempt)).await;
+      attempt += 1;
+    }
+  }
+
+  /// Sign
+  /// Hex HMAC-SHA256 of the query with the api secret
+  fn sign(api_secret: &str, query: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(api_secret.as_bytes()).unwrap();
+    mac.update(query.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+  }
+
+  /// Credentials
+  /// Api key and secret for signed endpoints, rejected as Binance would when either is missing
+  fn credentials(&self, path: &str) -> Result<(String, String), SmartError> {
+    match (&self.config.api_key, &self.config.api_secret) {
+      (Some(api_key), Some(api_secret)) => Ok((api_key.clone(), api_secret.clone())),
+      _ => Err(SmartError::Binance(BinanceError {
+        path: path.to_string(),
+        status: 401,
+        code: -2014,
+        msg: "Binance api key or secret not found in environment or .env file".to_string()
+      }))
+    }
+  }
+
+  /// Fetch Orderbook
+  /// Retrieves both sides of the orderbook, bids best first and asks best first
+  pub async fn fetch_orderbook(&self, symbol: &str) -> Result<OrderBook, SmartError> {
+    let url: String = format!("{}/api/v3/depth?symbol={}", self.config.rest_url, symbol);
+    let resp: reqwest::Response = self.send_with_retry(|| self.client.get(&url)).await?;
+
+    let depth: DepthResponse = parse_response("depth", &resp.text().await?)?;
+    let mut bids = parse_levels(&depth.bids)?;
+    let mut asks = parse_levels(&depth.asks)?;
+    bids.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    asks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    Ok(OrderBook { bids, asks, timestamp })
+  }
+
+  /// Refresh Orderbooks
+  /// Refetches cached orderbooks older than ORDERBOOK_CACHE_MS for the given symbols
+  pub async fn refresh_orderbooks(&mut self, symbols: &[String]) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    let stale: Vec<&String> = symbols.iter()
+      .filter(|symbol| self.orderbooks.get(*symbol).is_none_or(|book| now - book.timestamp > ORDERBOOK_CACHE_MS))
+      .collect();
+
+    let futures: Vec<_> = stale.iter().map(|symbol| self.fetch_orderbook(symbol)).collect();
+    let results: Vec<Result<OrderBook, SmartError>> = join_all(futures).await;
+    for (symbol, result) in stale.iter().zip(results) {
+      match result {
+        Ok(orderbook) => { self.orderbooks.insert(symbol.to_string(), orderbook); },
+        Err(e) => eprintln!("Error fetching order book: {:?}", e)
+      }
+    }
+  }
+
+  /// Rebuild Exchange Rates
+  /// Rebuilds edge rates from the top of book or cached orderbooks depending on GRAPH_MODE
+  pub fn rebuild_exchange_rates(&mut self) {
+    self.exchange_rates = match GRAPH_MODE {
+      GraphMode::TopOfBook => helpers::create_exchange_rates(&self.book_tickers, &self.fees),
+      GraphMode::Vwap => {
+        let notionals = helpers::create_asset_notionals(&self.symbols, &self.prices, VWAP_NOTIONAL_USD);
+        helpers::create_vwap_exchange_rates(&self.book_tickers, &self.orderbooks, &notionals, &self.fees)
+      }
+    };
+  }
+
+  /// Save Snapshot
+  /// Records symbols, prices, rates and any sampled orderbooks so detection can be replayed offline
+  #[allow(dead_code)]
+  pub fn save_snapshot(&self, path: &str) -> Result<(), SmartError> {
+    BinanceSnapshot::from(self).save(path)
+  }
 }
 
 #[async_trait]
 impl ApiCalls for Binance {
   async fn new() -> Self {
-    let symbols = Self::fetch_symbols().await.expect("Failed to fetch Binance symbols");
-    let prices = Self::fetch_prices().await.expect("Failed to fetch Binance prices");
-    let exchange_rates = helpers::create_exchange_rates(&symbols, &prices);
-    Self { symbols, prices, exchange_rates }
+    Self::with_config(BinanceConfig::from_constants()).await
   }
   
   /// Fetch Binance Symbols
   /// Retrieves Base and Quote symbol information so symbols can be broken up
-  async fn fetch_symbols() -> Result<HashMap<String, SymbolInfo>, SmartError> {
-    let url: &str = "https://api.binance.com/api/v3/exchangeInfo";
-    let response: reqwest::Response = reqwest::get(url).await?;
-    let data: serde_json::Value = response.json().await.unwrap();
+  async fn fetch_symbols(&self) -> Result<HashMap<String, SymbolInfo>, SmartError> {
+    let url: String = format!("{}/api/v3/exchangeInfo", self.config.rest_url);
+    let response: reqwest::Response = self.send_with_retry(|| self.client.get(&url)).await?;
+    let exchange_info: ExchangeInfoResponse = parse_response("exchangeInfo", &response.text().await?)?;
+    self.limiter.set_limits(&exchange_info.rate_limits);
     let mut symbols: HashMap<String, SymbolInfo> = HashMap::new();
 
-    if let Some(symbol_infos) = data["symbols"].as_array() {
-      for symbol_info in symbol_infos {
-        if symbol_info["status"] == "TRADING" && symbol_info["isSpotTradingAllowed"].as_bool().unwrap_or(false) {
-          let symbol = symbol_info["symbol"].as_str().unwrap_or_default().to_string();
-          let base_asset = symbol_info["baseAsset"].as_str().unwrap_or_default().to_string();
-          let quote_asset = symbol_info["quoteAsset"].as_str().unwrap_or_default().to_string();
-          let base_asset_precision = symbol_info["baseAssetPrecision"].as_u64().unwrap_or_default() as u8;
-          let quote_asset_precision = symbol_info["quoteAssetPrecision"].as_u64().unwrap_or_default() as u8;
-          
-          // Guard: Ensure no fiat currency
-          if FIAT_EXCLUSION.contains(&base_asset.as_str()) || FIAT_EXCLUSION.contains(&quote_asset.as_str()) { continue; }
-
-          // Extract minQty, maxQty, and stepSize from LOT_SIZE filter
-          let lot_size_filter = symbol_info["filters"].as_array().unwrap()
-            .iter()
-            .find(|&f| f["filterType"] == "LOT_SIZE")
-            .unwrap_or(&serde_json::Value::Null);
-          let min_qty = lot_size_filter["minQty"].as_str().unwrap_or_default().to_string();
-          let max_qty = lot_size_filter["maxQty"].as_str().unwrap_or_default().to_string();
-          let step_size = lot_size_filter["stepSize"].as_str().unwrap_or_default().to_string();
-
-          // Extract min_notional and max_notional from MIN_NOTIONAL filter
-          let notional_filter = symbol_info["filters"].as_array().unwrap()
-            .iter()
-            .find(|&f| f["filterType"] == "NOTIONAL")
-            .unwrap_or(&serde_json::Value::Null);
-          let min_notional = notional_filter["minNotional"].as_str().unwrap_or_default().to_string();
-          let max_notional = notional_filter["maxNotional"].as_str().unwrap_or_default().to_string();
-
-          let symbol_info = SymbolInfo {
-              symbol,
-              base_asset,
-              quote_asset,
-              base_asset_precision,
-              quote_asset_precision,
-              min_qty,
-              max_qty,
-              min_notional,
-              max_notional,
-              step_size,
-          };
-
-          symbols.insert(symbol_info.symbol.clone(), symbol_info);
-        }
+    for symbol_info in exchange_info.symbols {
+      if symbol_info.status == "TRADING" && symbol_info.is_spot_trading_allowed {
+
+        // Guard: Ensure no fiat currency
+        if FIAT_EXCLUSION.contains(&symbol_info.base_asset.as_str()) || FIAT_EXCLUSION.contains(&symbol_info.quote_asset.as_str()) { continue; }
+
+        // Type every filter the order will be checked against
+        let info = SymbolInfo::try_from(&symbol_info)?;
+        symbols.insert(info.symbol.clone(), info);
       }
     }
 
@@ -89,68 +241,57 @@ impl ApiCalls for Binance {
   
   /// Fetch Binance Prices
   /// Retrieves current prices for assets
-  async fn fetch_prices() -> Result<HashMap<String, f64>, SmartError> {
-    let url = "https://api.binance.com/api/v3/ticker/price";
-    let response = reqwest::get(url).await?;
-    let data: serde_json::Value = response.json().await?;
+  async fn fetch_prices(&self) -> Result<HashMap<String, f64>, SmartError> {
+    let url = format!("{}/api/v3/ticker/price", self.config.rest_url);
+    let response = self.send_with_retry(|| self.client.get(&url)).await?;
+    let price_items: Vec<TickerPriceResponse> = parse_response("ticker/price", &response.text().await?)?;
     let mut prices = HashMap::new();
-    if let Some(price_items) = data.as_array() {
-      for item in price_items {
-        let symbol = item["symbol"].as_str().unwrap_or_default().to_string();
-        let price = item["price"].as_str().unwrap_or_default().parse::<f64>()?;
-        prices.insert(symbol, price);
-      }
+    for item in price_items {
+      let price = item.price.parse::<f64>()?;
+      prices.insert(item.symbol, price);
     }
   
     Ok(prices)
   }
 
+  /// Fetch Binance Book Tickers
+  /// Retrieves best bid and ask (with sizes) for every tradable symbol
+  async fn fetch_book_tickers(&self, symbols: &HashMap<String, SymbolInfo>) -> Result<HashMap<String, ExchangeRate>, SmartError> {
+    let url = format!("{}/api/v3/ticker/bookTicker", self.config.rest_url);
+    let response = self.send_with_retry(|| self.client.get(&url)).await?;
+    let book_items: Vec<BookTickerResponse> = parse_response("ticker/bookTicker", &response.text().await?)?;
+    let mut book_tickers = HashMap::new();
+    for item in book_items {
+
+      // Guard: Ensure symbol is tradable
+      let Some(symbol_info) = symbols.get(&item.symbol) else { continue };
+
+      let best_bid_price = item.bid_price.parse::<f64>()?;
+      let best_bid_size = item.bid_qty.parse::<f64>()?;
+      let best_ask_price = item.ask_price.parse::<f64>()?;
+      let best_ask_size = item.ask_qty.parse::<f64>()?;
+      let book_ticker = ExchangeRate::from_book_ticker(symbol_info, best_bid_price, best_bid_size, best_ask_price, best_ask_size);
+      book_tickers.insert(symbol_info.symbol.clone(), book_ticker);
+    }
+
+    Ok(book_tickers)
+  }
+
   /// Get Orderbook Depth
   /// Retrieves orderbook depth for either bids or asks
   async fn get_orderbook_depth(&self, symbol: &str, direction: &Direction) -> Result<Vec<(f64, f64)>, SmartError> {
-    let url: String = format!("https://api.binance.com/api/v3/depth?symbol={}", symbol);
-    let resp: reqwest::Response = reqwest::get(&url).await?;
-
-    if resp.status().is_success() {
-      let data_res: Result<serde_json::Value, reqwest::Error> = resp.json().await;
-      let data = match data_res {
-        Ok(data) => data,
-        Err(e) => panic!("Failed to extract orderbook: {:?}", e)
-      };
-
-      let book: &str = direction.orderbook();
-      let order_book = data[book].as_array().ok_or("Invalid JSON structure").map_err(|e| SmartError::Runtime(e.to_string()))?;
-
-      let mut result = vec![];
-      for item in order_book {
-        let price = item[0].as_str().ok_or("Invalid price format").map_err(|e| SmartError::Runtime(e.to_string()))?.parse::<f64>()?;
-        let qty = item[1].as_str().ok_or("Invalid quantity format").map_err(|e| SmartError::Runtime(e.to_string()))?.parse::<f64>()?;
-        result.push((price, qty));
-      }
-
-      if book == "asks" {
-        result.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-      } else {
-        result.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
-      }
-
-      Ok(result)
-    } else {
-      Err(SmartError::Runtime("Failed to fetch data".to_string()))
+    let orderbook = self.fetch_orderbook(symbol).await?;
+    match direction {
+      Direction::Forward => Ok(orderbook.bids),
+      Direction::Reverse => Ok(orderbook.asks),
     }
   }
 
   /// Place Market Order
   /// Places market order
   /// Side BUY / SELL
-  async fn place_market_order(&self, symbol: &str, direction: &Direction, quantity: f64) -> Result<(String, f64, f64), SmartError> {
-    dotenv().ok();
-
-    let api_key = env::var("BINANCE_API_KEY")
-      .expect("BINANCE_API_KEY not found in .env file");
-
-    let api_secret = env::var("BINANCE_API_SECRET")
-      .expect("BINANCE_API_SECRET not found in .env file");
+  async fn place_market_order(&self, symbol: &str, direction: &Direction, quantity: Decimal) -> Result<(String, Decimal, Decimal), SmartError> {
+    let (api_key, api_secret) = self.credentials("/api/v3/order")?;
 
     let order_type = "MARKET";
     let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
@@ -164,123 +305,217 @@ impl ApiCalls for Binance {
     };
 
     // Create signature
-    let mut mac = Hmac::<Sha256>::new_from_slice(api_secret.as_bytes()).unwrap();
-    mac.update(query.as_bytes());
-    let signature = hex::encode(mac.finalize().into_bytes());
+    let signature = Self::sign(&api_secret, &query);
 
     // Append signature to query
     query.push_str("&signature=");
     query.push_str(&signature);
 
-    // Send request
-    let client = reqwest::Client::new();
-    let res: reqwest::Response = client.post("https://api.binance.com/api/v3/order")
+    // Send request, never retried as the order may already have been placed
+    let request = self.client.post(format!("{}/api/v3/order", self.config.rest_url))
       .header("X-MBX-APIKEY", api_key)
-      .body(query)
-      .send()
-      .await?;
+      .body(query);
+    let res: reqwest::Response = self.send(request).await?;
 
     let res_text: String = res.text().await?;
+    let order: OrderResponse = parse_response("order", &res_text)?;
+    let result = order.result
+      .ok_or(SmartError::Runtime(format!("Order {} returned no execution result (ACK response)", order.order_id)))?;
+    let executed_base_qty: Decimal = result.executed_qty.parse::<Decimal>()?;
+    let executed_quote_qty: Decimal = result.cummulative_quote_qty.parse::<Decimal>()?;
 
-    dbg!(&res_text);
-
-    let res_json: serde_json::Value = serde_json::from_str(res_text.as_str())?;
-    let status: String = res_json["status"].as_str().expect("Status not returned for order placed").to_owned();
-    let executed_base_qty_str: &str = res_json["executedQty"].as_str().expect("Missing executed base quantity");
-    let executed_base_qty: f64 = executed_base_qty_str.parse::<f64>().expect("Failed to parse executed base amount out");
-    let executed_quote_qty_str: &str = res_json["cummulativeQuoteQty"].as_str().expect("Missing executed quote quantity");
-    let executed_quote_qty: f64 = executed_quote_qty_str.parse::<f64>().expect("Failed to parse executed quote amount out");
-
-    Ok((status, executed_base_qty, executed_quote_qty))
+    Ok((result.status, executed_base_qty, executed_quote_qty))
   }
 
   /// Get Asset Account Balance
   /// Retrieves Spot Balance for given asset (used for checking amounts available to trade)
-  async fn get_asset_account_balance(&self, asset: &str) -> Result<f64, SmartError> {
-    dotenv().ok();
-
-    let api_key = env::var("BINANCE_API_KEY")
-      .expect("BINANCE_API_KEY not found in .env file");
-
-    let api_secret = env::var("BINANCE_API_SECRET")
-      .expect("BINANCE_API_SECRET not found in .env file");
-
-    // Constuct Query
-    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
-    let mut query = format!("timestamp={}", timestamp);
-
-    // Create signature
-    let mut mac = Hmac::<Sha256>::new_from_slice(api_secret.as_bytes()).unwrap();
-    mac.update(query.as_bytes());
-    let signature = hex::encode(mac.finalize().into_bytes());
-
-    // Append signature to query
-    query.push_str("&signature=");
-    query.push_str(&signature);
+  async fn get_asset_account_balance(&self, asset: &str) -> Result<Decimal, SmartError> {
+    let (api_key, api_secret) = self.credentials("/api/v3/account")?;
+
+    // Construct and sign the query, again on every attempt
+    let build = || {
+      let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis().to_string();
+      let query = format!("timestamp={}", timestamp);
+      let signature = Self::sign(&api_secret, &query);
+      let url = format!("{}/api/v3/account?{}&signature={}", self.config.rest_url, query, signature);
+      self.client.get(url).header("X-MBX-APIKEY", &api_key)
+    };
 
     // Send request
-    let url = format!("https://api.binance.com/api/v3/account?{}", query);
-    let client = reqwest::Client::new();
-    let res: reqwest::Response = client.get(url)
-      .header("X-MBX-APIKEY", api_key)
-      .send()
-      .await?;
+    let res: reqwest::Response = self.send_with_retry(build).await?;
 
     let res_text = res.text().await?;
-    let account_info: serde_json::Value = serde_json::from_str(&res_text)?;
-    let balances: &Vec<serde_json::Value> = account_info["balances"].as_array().expect("Failed to find balances");
-    let mut free_balance = 0.0;
-    for item in balances {
-      let coin = item["asset"].as_str().expect("Failed to locate asset");
-      if coin == asset {
-        free_balance = item["free"].as_str().expect("Failed to locate available amount").parse::<f64>().expect("Filed to parse balance");
-        break;
-      }
-    }    
+    let account_info: AccountResponse = parse_response("account", &res_text)?;
+    let free_balance = match account_info.balances.iter().find(|balance| balance.asset == asset) {
+      Some(balance) => balance.free.parse::<Decimal>()?,
+      None => Decimal::ZERO
+    };
 
     Ok(free_balance)
   }
 }
 
-impl BellmanFordEx for Binance {
-  fn run_bellman_ford_single(&self) -> Option<Vec<Edge>> {
-    let bf = BellmanFord::new(&self.exchange_rates);
-    let cycle = bf.find_negative_cycle();
-    cycle
-  }
-
-  fn run_bellman_ford_multi(&self) -> Vec<Vec<Edge>> {
-    let bf = BellmanFord::new(&self.exchange_rates);
-    let cycles = bf.find_all_negative_cycles();
-    cycles
-  }
-}
+impl BellmanFordEx for Binance {}
 
 impl ExchangeData for Binance {
   fn symbols(&self) -> &HashMap<String, SymbolInfo> { &self.symbols }
   fn prices(&self) -> &HashMap<String, f64> { &self.prices }
-  fn exchange_rates(&self) -> &Vec<(String, String, f64)> { &self.exchange_rates }
+  fn book_tickers(&self) -> &HashMap<String, ExchangeRate> { &self.book_tickers }
+  fn exchange_rates(&self) -> &Vec<DirectedRate> { &self.exchange_rates }
+  fn fees(&self) -> &FeeSchedule { &self.fees }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::models::{BinanceEnvironment, BinanceErrorKind};
+  use super::super::mock_binance::MockBinance;
+  use rust_decimal_macros::dec;
+
+  #[test]
+  fn it_resolves_urls_and_credentials_per_environment() {
+    let production = BinanceConfig::from_environment(BinanceEnvironment::Production);
+    let testnet = BinanceConfig::from_environment(BinanceEnvironment::Testnet);
+    assert_eq!(production.rest_url, "https://api.binance.com");
+    assert_eq!(testnet.rest_url, "https://testnet.binance.vision");
+    assert_eq!(testnet.ws_url, "wss://stream.testnet.binance.vision");
+
+    std::env::set_var("BINANCE_CUSTOM_API_KEY", "mock-key");
+    let custom = BinanceConfig::from_environment(BinanceEnvironment::Custom { rest_url: "http://127.0.0.1:8080", ws_url: "ws://127.0.0.1:8080" });
+    assert_eq!(custom.rest_url, "http://127.0.0.1:8080");
+    assert_eq!(custom.api_key.as_deref(), Some("mock-key"));
+  }
+
+  #[tokio::test]
+  async fn it_runs_against_mock_server() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
+    assert_eq!(exchange.symbols.len(), 13);
+    assert_eq!(exchange.book_tickers.len(), 13);
+    assert!(exchange.run_bellman_ford_bounded().iter().all(|cycle| cycle.iter().any(|leg| leg.symbol == "SOLETH")));
+
+    let bids = exchange.get_orderbook_depth("SOLETH", &Direction::Forward).await.unwrap();
+    assert_eq!(bids.len(), 5);
+    assert!(bids[0].0 > bids[1].0);
+
+    let (status, base_qty, quote_qty) = exchange.place_market_order("SOLUSDT", &Direction::Reverse, dec!(20)).await.unwrap();
+    assert_eq!(status, "FILLED");
+    assert!(base_qty > Decimal::ZERO && quote_qty == dec!(20));
+    assert_eq!(exchange.get_asset_account_balance("USDT").await.unwrap(), dec!(80));
+    assert!(mock.state.lock().unwrap().orders[0].contains("&quoteOrderQty=20&"));
+    assert!(mock.state.lock().unwrap().orders[0].contains("signature="));
+  }
+
+  #[tokio::test]
+  async fn it_refreshes_in_place_keeping_client_and_limiter() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let mut exchange: Binance = Binance::unloaded(mock.config.clone());
+    let limiter = exchange.limiter.clone();
+    exchange.refresh().await.unwrap();
+
+    mock.state.lock().unwrap().snapshot.prices.insert("SOLUSDT".to_string(), 1.0);
+    exchange.refresh().await.unwrap();
+    assert_eq!(exchange.prices["SOLUSDT"], 1.0);
+    assert!(Arc::ptr_eq(&limiter, &exchange.limiter));
+  }
+
+  #[tokio::test]
+  async fn it_rejects_signed_calls_without_credentials() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let config = BinanceConfig { api_secret: None, ..mock.config.clone() };
+    let exchange: Binance = Binance::with_config(config).await;
+
+    match exchange.get_asset_account_balance("USDT").await {
+      Err(SmartError::Binance(error)) => assert_eq!(error.kind(), BinanceErrorKind::Unauthorized),
+      result => panic!("Expected an unauthorized error, got {:?}", result)
+    }
+    assert!(exchange.place_market_order("SOLUSDT", &Direction::Reverse, dec!(20)).await.is_err());
+    assert!(mock.requests_to("/api/v3/order").is_empty());
+  }
+
+  #[tokio::test]
+  async fn it_retries_idempotent_requests_but_not_orders() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
+    let unavailable = (503, serde_json::json!({ "code": -1001, "msg": "Internal error; unable to process your request." }));
+
+    // Transient 5xx on depth is retried through
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/depth".to_string(), vec![unavailable.clone(); 2].into());
+    let bids = exchange.get_orderbook_depth("SOLETH", &Direction::Forward).await.unwrap();
+    assert_eq!(bids.len(), 5);
+    assert_eq!(mock.requests_to("/api/v3/depth").len(), 3);
+
+    // Retries are bounded
+    mock.state.lock().unwrap().errors.insert("/api/v3/depth".to_string(), unavailable.clone());
+    assert!(exchange.fetch_orderbook("SOLETH").await.is_err());
+    assert_eq!(mock.requests_to("/api/v3/depth").len(), 3 + 1 + HTTP_MAX_RETRIES as usize);
+
+    // Orders are sent once
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/order".to_string(), vec![unavailable.clone()].into());
+    assert!(exchange.place_market_order("SOLUSDT", &Direction::Reverse, dec!(20)).await.is_err());
+    assert_eq!(mock.requests_to("/api/v3/order").len(), 1);
+    assert_eq!(exchange.get_asset_account_balance("USDT").await.unwrap(), dec!(100));
+
+    // A rejected timestamp is retried with a freshly signed request
+    let timestamp = (400, serde_json::json!({ "code": -1021, "msg": "Timestamp for this request is outside of the recvWindow." }));
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/account".to_string(), vec![timestamp].into());
+    assert_eq!(exchange.get_asset_account_balance("USDT").await.unwrap(), dec!(100));
+    let account_requests = mock.requests_to("/api/v3/account");
+    assert_eq!(account_requests.len(), 3);
+    assert_ne!(account_requests[1], account_requests[2]);
+
+    // A read that keeps failing is unavailable, only an order is left in an unknown state
+    mock.state.lock().unwrap().errors.insert("/api/v3/account".to_string(), unavailable);
+    match exchange.get_asset_account_balance("USDT").await {
+      Err(SmartError::Binance(error)) => {
+        assert_eq!(error.kind(), BinanceErrorKind::Unavailable);
+        assert!(!SmartError::Binance(error).is_fatal());
+      },
+      result => panic!("Expected an unavailable error, got {:?}", result)
+    }
+  }
+
+  #[tokio::test]
+  async fn it_governs_requests_with_the_rate_limiter() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
+    assert_eq!(exchange.limiter.limits().len(), 3);
+    assert!(exchange.limiter.limits()[0].used >= request_weight("/api/v3/exchangeInfo"));
+
+    // 429 waits out Retry-After then succeeds
+    let too_many = (429, serde_json::json!({ "code": -1003, "msg": "Too many requests." }));
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/depth".to_string(), vec![too_many].into());
+    let start = std::time::Instant::now();
+    assert!(exchange.fetch_orderbook("SOLETH").await.is_ok());
+    assert!(start.elapsed() >= std::time::Duration::from_millis(900));
+
+    // 418 ban is longer than the limiter will queue, later calls never reach Binance
+    let banned = (418, serde_json::json!({ "code": -1003, "msg": "Way too many requests; IP banned." }));
+    mock.state.lock().unwrap().retry_after_s = 120;
+    mock.state.lock().unwrap().transient_errors.insert("/api/v3/depth".to_string(), vec![banned].into());
+    assert!(exchange.fetch_orderbook("SOLETH").await.is_err());
+    let depth_requests = mock.requests_to("/api/v3/depth").len();
+    assert!(matches!(exchange.fetch_orderbook("SOLETH").await, Err(SmartError::RateLimited(_))));
+    assert!(matches!(exchange.get_asset_account_balance("USDT").await, Err(SmartError::RateLimited(_))));
+    assert_eq!(mock.requests_to("/api/v3/depth").len(), depth_requests);
+  }
 
   #[tokio::test]
   async fn it_creates_binance_instance() {
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    let exchange: Binance = Binance::new().await;
-    assert!(exchange.symbols.len() > 0);
-    assert!(exchange.prices.len() > 0);
-    assert!(exchange.exchange_rates.len() > 0);
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
+    assert!(!exchange.symbols.is_empty());
+    assert!(!exchange.prices.is_empty());
+    assert!(!exchange.book_tickers.is_empty());
+    assert!(!exchange.exchange_rates.is_empty());
   }
 
   #[tokio::test]
   async fn it_extracts_binance_orderbook() {
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    let exchange: Binance = Binance::new().await;
-    let orderbook_bids: Vec<(f64, f64)> = exchange.get_orderbook_depth("BTCUSDT", &Direction::Forward).await.unwrap();
-    let orderbook_asks: Vec<(f64, f64)> = exchange.get_orderbook_depth("BTCUSDT", &Direction::Reverse).await.unwrap();
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
+    let orderbook_bids: Vec<(f64, f64)> = exchange.get_orderbook_depth("ETHUSDT", &Direction::Forward).await.unwrap();
+    let orderbook_asks: Vec<(f64, f64)> = exchange.get_orderbook_depth("ETHUSDT", &Direction::Reverse).await.unwrap();
     assert!(orderbook_asks[0].0 > orderbook_bids[0].0);
     assert!(orderbook_asks[0].0 < orderbook_asks[1].0);
     assert!(orderbook_bids[0].0 > orderbook_bids[1].0);
@@ -288,29 +523,29 @@ mod test {
 
   #[tokio::test]
   async fn it_runs_bellman_ford_single_and_multi() {
-    std::thread::sleep(std::time::Duration::from_millis(100));
-    let exchange: Binance = Binance::new().await;
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let exchange: Binance = Binance::with_config(mock.config.clone()).await;
     let cycle = exchange.run_bellman_ford_single();
     let cycles = exchange.run_bellman_ford_multi();
     assert!(cycle.is_some());
-    assert!(cycles.len() > 0);
+    assert!(!cycles.is_empty());
   }
 
   // #[tokio::test]
   // async fn it_places_a_trade() {
   //   let exchange: Binance = Binance::new().await;
   //   let symbol = "BTCUSDT";
-  //   let quantity = 20.0; // 20 USDT
+  //   let quantity = dec!(20); // 20 USDT
   //   let direction = Direction::Reverse;
 
   //   let symbol_info: &SymbolInfo = exchange.symbols.get(symbol).unwrap();
-  //   let price: f64 = *exchange.prices.get(symbol).unwrap();
-  //   let quantity: f64 = helpers::validate_quantity(symbol_info, quantity, price, &direction).unwrap();
+  //   let price: Decimal = helpers::to_decimal(*exchange.prices.get(symbol).unwrap());
+  //   let quantity: Decimal = helpers::validate_quantity(symbol_info, quantity, price, price, &direction).unwrap();
 
   //   let (status, base_amount_out, quote_amount_out) = exchange.place_market_order(symbol, &direction, quantity).await.unwrap();
   //   assert!(status.as_str() == "FILLED");
-  //   assert!(base_amount_out > 0.0);
-  //   assert!(quote_amount_out > 0.0);
+  //   assert!(base_amount_out > Decimal::ZERO);
+  //   assert!(quote_amount_out > Decimal::ZERO);
   // }
 
   // #[tokio::test]
@@ -318,6 +553,6 @@ mod test {
   //   let exchange: Binance = Binance::new().await;
   //   let asset = "USDT";
   //   let balance = exchange.get_asset_account_balance(asset).await.unwrap();
-  //   assert!(balance > 0.0);
+  //   assert!(balance > Decimal::ZERO);
   // }
 }
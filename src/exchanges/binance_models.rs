@@ -0,0 +1,342 @@
+use crate::models::{LotSizeFilter, NotionalFilter, PercentPriceBySideFilter, PriceFilter, SmartError, SymbolInfo};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Parse Response
+/// Deserializes a Binance response body, naming the endpoint and keeping the body when the shape changed
+pub fn parse_response<R: DeserializeOwned>(endpoint: &str, body: &str) -> Result<R, SmartError> {
+  serde_json::from_str(body).map_err(|source| SmartError::ResponseParse {
+    endpoint: endpoint.to_string(),
+    body: body.chars().take(500).collect(),
+    source
+  })
+}
+
+/// Parse Levels
+/// Parses [price, qty] string pairs
+pub fn parse_levels(levels: &[(String, String)]) -> Result<Vec<(f64, f64)>, SmartError> {
+  levels.iter().map(|(price, qty)| Ok((price.parse::<f64>()?, qty.parse::<f64>()?))).collect()
+}
+
+/// Exchange Info Response
+/// GET /api/v3/exchangeInfo
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExchangeInfoResponse {
+  pub timezone: String,
+  pub server_time: Option<u64>,
+  pub rate_limits: Vec<RateLimitResponse>,
+  pub symbols: Vec<SymbolResponse>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitResponse {
+  pub rate_limit_type: String,
+  pub interval: String,
+  pub interval_num: u64,
+  pub limit: u32
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SymbolResponse {
+  pub symbol: String,
+  pub status: String,
+  pub base_asset: String,
+  pub base_asset_precision: u8,
+  pub quote_asset: String,
+  pub quote_asset_precision: u8,
+  #[serde(default)]
+  pub order_types: Vec<String>,
+  #[serde(default)]
+  pub quote_order_qty_market_allowed: bool,
+  #[serde(default)]
+  pub is_spot_trading_allowed: bool,
+  #[serde(default)]
+  pub filters: Vec<SymbolFilter>
+}
+
+impl TryFrom<&SymbolResponse> for SymbolInfo {
+  type Error = SmartError;
+
+  /// Try From
+  /// Types the filters market orders are checked against, a missing LOT_SIZE or NOTIONAL leaves the symbol unrestricted
+  /// Legacy MIN_NOTIONAL stands in for NOTIONAL on symbols which only carry the older filter
+  fn try_from(symbol_info: &SymbolResponse) -> Result<Self, Self::Error> {
+    let mut info = SymbolInfo {
+      symbol: symbol_info.symbol.clone(),
+      base_asset: symbol_info.base_asset.clone(),
+      quote_asset: symbol_info.quote_asset.clone(),
+      base_asset_precision: symbol_info.base_asset_precision,
+      quote_asset_precision: symbol_info.quote_asset_precision,
+      lot_size: LotSizeFilter::default(),
+      market_lot_size: None,
+      notional: NotionalFilter::default(),
+      price_filter: None,
+      percent_price_by_side: None,
+      max_num_orders: None,
+      quote_order_qty_market_allowed: symbol_info.quote_order_qty_market_allowed
+    };
+
+    let mut has_notional = false;
+    for filter in &symbol_info.filters {
+      match filter {
+        SymbolFilter::LotSize { min_qty, max_qty, step_size } => {
+          info.lot_size = LotSizeFilter { min_qty: min_qty.parse()?, max_qty: max_qty.parse()?, step_size: step_size.parse()? };
+        },
+        SymbolFilter::MarketLotSize { min_qty, max_qty, step_size } => {
+          info.market_lot_size = Some(LotSizeFilter { min_qty: min_qty.parse()?, max_qty: max_qty.parse()?, step_size: step_size.parse()? });
+        },
+        SymbolFilter::Notional { min_notional, apply_min_to_market, max_notional, apply_max_to_market, .. } => {
+          info.notional = NotionalFilter {
+            min_notional: min_notional.parse()?,
+            apply_min_to_market: *apply_min_to_market,
+            max_notional: max_notional.parse()?,
+            apply_max_to_market: *apply_max_to_market
+          };
+          has_notional = true;
+        },
+        SymbolFilter::MinNotional { min_notional, apply_to_market, .. } if !has_notional => {
+          info.notional = NotionalFilter { min_notional: min_notional.parse()?, apply_min_to_market: *apply_to_market, ..NotionalFilter::default() };
+        },
+        SymbolFilter::PriceFilter { min_price, max_price, tick_size } => {
+          info.price_filter = Some(PriceFilter { min_price: min_price.parse()?, max_price: max_price.parse()?, tick_size: tick_size.parse()? });
+        },
+        SymbolFilter::PercentPriceBySide { bid_multiplier_up, bid_multiplier_down, ask_multiplier_up, ask_multiplier_down, .. } => {
+          info.percent_price_by_side = Some(PercentPriceBySideFilter {
+            bid_multiplier_up: bid_multiplier_up.parse()?,
+            bid_multiplier_down: bid_multiplier_down.parse()?,
+            ask_multiplier_up: ask_multiplier_up.parse()?,
+            ask_multiplier_down: ask_multiplier_down.parse()?
+          });
+        },
+        SymbolFilter::MaxNumOrders { max_num_orders } => info.max_num_orders = Some(*max_num_orders),
+        _ => {}
+      }
+    }
+
+    Ok(info)
+  }
+}
+
+/// Symbol Filter
+/// Every symbol filter type Binance documents, tagged by filterType
+/// Filter types added after these are kept as Unknown rather than failing the whole exchangeInfo
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SymbolFilter {
+  #[serde(rename_all = "camelCase")]
+  PriceFilter { min_price: String, max_price: String, tick_size: String },
+  #[serde(rename_all = "camelCase")]
+  PercentPrice { multiplier_up: String, multiplier_down: String, avg_price_mins: u32 },
+  #[serde(rename_all = "camelCase")]
+  PercentPriceBySide {
+    bid_multiplier_up: String,
+    bid_multiplier_down: String,
+    ask_multiplier_up: String,
+    ask_multiplier_down: String,
+    avg_price_mins: u32
+  },
+  #[serde(rename_all = "camelCase")]
+  LotSize { min_qty: String, max_qty: String, step_size: String },
+  #[serde(rename_all = "camelCase")]
+  MinNotional { min_notional: String, apply_to_market: bool, avg_price_mins: u32 },
+  #[serde(rename_all = "camelCase")]
+  Notional { min_notional: String, apply_min_to_market: bool, max_notional: String, apply_max_to_market: bool, avg_price_mins: u32 },
+  IcebergParts { limit: u32 },
+  #[serde(rename_all = "camelCase")]
+  MarketLotSize { min_qty: String, max_qty: String, step_size: String },
+  #[serde(rename_all = "camelCase")]
+  MaxNumOrders { max_num_orders: u32 },
+  #[serde(rename_all = "camelCase")]
+  MaxNumAlgoOrders { max_num_algo_orders: u32 },
+  #[serde(rename_all = "camelCase")]
+  MaxNumIcebergOrders { max_num_iceberg_orders: u32 },
+  #[serde(rename_all = "camelCase")]
+  MaxPosition { max_position: String },
+  #[serde(rename_all = "camelCase")]
+  TrailingDelta { min_trailing_above_delta: u32, max_trailing_above_delta: u32, min_trailing_below_delta: u32, max_trailing_below_delta: u32 },
+  #[serde(other)]
+  Unknown
+}
+
+/// Ticker Price Response
+/// Element of GET /api/v3/ticker/price
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickerPriceResponse {
+  pub symbol: String,
+  pub price: String
+}
+
+/// Book Ticker Response
+/// Element of GET /api/v3/ticker/bookTicker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookTickerResponse {
+  pub symbol: String,
+  pub bid_price: String,
+  pub bid_qty: String,
+  pub ask_price: String,
+  pub ask_qty: String
+}
+
+/// Depth Response
+/// GET /api/v3/depth, levels are [price, qty] string pairs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthResponse {
+  pub last_update_id: u64,
+  pub bids: Vec<(String, String)>,
+  pub asks: Vec<(String, String)>
+}
+
+/// Order Response
+/// POST /api/v3/order for every newOrderRespType
+/// ACK only carries the ids, RESULT adds the execution and FULL adds the fills
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResponse {
+  pub symbol: String,
+  pub order_id: u64,
+  #[serde(default)]
+  pub order_list_id: i64,
+  pub client_order_id: String,
+  pub transact_time: u64,
+  #[serde(flatten)]
+  pub result: Option<OrderResult>,
+  #[serde(default)]
+  pub fills: Vec<OrderFill>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderResult {
+  pub price: String,
+  pub orig_qty: String,
+  pub executed_qty: String,
+  pub cummulative_quote_qty: String,
+  pub status: String,
+  pub time_in_force: String,
+  #[serde(rename = "type")]
+  pub order_type: String,
+  pub side: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderFill {
+  pub price: String,
+  pub qty: String,
+  pub commission: String,
+  pub commission_asset: String,
+  #[serde(default)]
+  pub trade_id: u64
+}
+
+/// Error Response
+/// Body of every rejected request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+  pub code: i64,
+  pub msg: String
+}
+
+/// Account Response
+/// GET /api/v3/account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountResponse {
+  #[serde(default)]
+  pub can_trade: bool,
+  pub balances: Vec<AccountBalance>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountBalance {
+  pub asset: String,
+  pub free: String,
+  pub locked: String
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use rust_decimal_macros::dec;
+
+  #[test]
+  fn it_parses_every_filter_type() {
+    let symbol: SymbolResponse = parse_response("exchangeInfo", r#"{
+      "symbol": "ETHBTC", "status": "TRADING", "baseAsset": "ETH", "baseAssetPrecision": 8, "quoteAsset": "BTC", "quoteAssetPrecision": 8,
+      "orderTypes": ["LIMIT", "MARKET"], "quoteOrderQtyMarketAllowed": true, "isSpotTradingAllowed": true,
+      "filters": [
+        { "filterType": "PRICE_FILTER", "minPrice": "0.00001000", "maxPrice": "922327.00000000", "tickSize": "0.00001000" },
+        { "filterType": "PERCENT_PRICE", "multiplierUp": "5", "multiplierDown": "0.2", "avgPriceMins": 5 },
+        { "filterType": "PERCENT_PRICE_BY_SIDE", "bidMultiplierUp": "5", "bidMultiplierDown": "0.2", "askMultiplierUp": "5", "askMultiplierDown": "0.2", "avgPriceMins": 5 },
+        { "filterType": "LOT_SIZE", "minQty": "0.00010000", "maxQty": "100000.00000000", "stepSize": "0.00010000" },
+        { "filterType": "MIN_NOTIONAL", "minNotional": "0.00010000", "applyToMarket": true, "avgPriceMins": 5 },
+        { "filterType": "NOTIONAL", "minNotional": "0.00010000", "applyMinToMarket": true, "maxNotional": "9000000.00000000", "applyMaxToMarket": false, "avgPriceMins": 5 },
+        { "filterType": "ICEBERG_PARTS", "limit": 10 },
+        { "filterType": "MARKET_LOT_SIZE", "minQty": "0.00000000", "maxQty": "2000.00000000", "stepSize": "0.00000000" },
+        { "filterType": "MAX_NUM_ORDERS", "maxNumOrders": 200 },
+        { "filterType": "MAX_NUM_ALGO_ORDERS", "maxNumAlgoOrders": 5 },
+        { "filterType": "MAX_NUM_ICEBERG_ORDERS", "maxNumIcebergOrders": 5 },
+        { "filterType": "MAX_POSITION", "maxPosition": "10.00000000" },
+        { "filterType": "TRAILING_DELTA", "minTrailingAboveDelta": 10, "maxTrailingAboveDelta": 2000, "minTrailingBelowDelta": 10, "maxTrailingBelowDelta": 2000 },
+        { "filterType": "SOME_FUTURE_FILTER", "value": "1" }
+      ]
+    }"#).unwrap();
+
+    assert_eq!(symbol.filters.len(), 14);
+    assert_eq!(symbol.filters[13], SymbolFilter::Unknown);
+
+    let info = SymbolInfo::try_from(&symbol).unwrap();
+    assert_eq!(info.lot_size, LotSizeFilter { min_qty: dec!(0.0001), max_qty: dec!(100000), step_size: dec!(0.0001) });
+    assert_eq!(info.market_lot_size, Some(LotSizeFilter { min_qty: dec!(0), max_qty: dec!(2000), step_size: dec!(0) }));
+    assert_eq!((info.notional.min_notional, info.notional.apply_max_to_market), (dec!(0.0001), false));
+    assert_eq!(info.price_filter.unwrap().tick_size.to_string(), "0.00001000");
+    assert_eq!(info.percent_price_by_side.unwrap().bid_multiplier_down, dec!(0.2));
+    assert_eq!(info.max_num_orders, Some(200));
+    assert!(info.quote_order_qty_market_allowed);
+
+    // NOTIONAL wins over MIN_NOTIONAL in either order, MIN_NOTIONAL alone still sets the minimum
+    let mut symbol = symbol;
+    symbol.filters.swap(4, 5);
+    let info = SymbolInfo::try_from(&symbol).unwrap();
+    assert_eq!(info.notional.max_notional, dec!(9000000));
+    symbol.filters.retain(|filter| !matches!(filter, SymbolFilter::Notional { .. }));
+    let info = SymbolInfo::try_from(&symbol).unwrap();
+    assert_eq!(info.notional, NotionalFilter { min_notional: dec!(0.0001), apply_min_to_market: true, ..NotionalFilter::default() });
+  }
+
+  #[test]
+  fn it_parses_ack_result_and_full_orders() {
+    let ack: OrderResponse = parse_response("order", r#"{ "symbol": "BTCUSDT", "orderId": 28, "orderListId": -1, "clientOrderId": "abc", "transactTime": 1507725176595 }"#).unwrap();
+    assert!(ack.result.is_none());
+
+    let full: OrderResponse = parse_response("order", r#"{
+      "symbol": "BTCUSDT", "orderId": 28, "orderListId": -1, "clientOrderId": "abc", "transactTime": 1507725176595,
+      "price": "0.00000000", "origQty": "10.00000000", "executedQty": "10.00000000", "cummulativeQuoteQty": "10.00000000",
+      "status": "FILLED", "timeInForce": "GTC", "type": "MARKET", "side": "SELL", "workingTime": 1507725176595, "selfTradePreventionMode": "NONE",
+      "fills": [{ "price": "4000.00000000", "qty": "1.00000000", "commission": "4.00000000", "commissionAsset": "USDT", "tradeId": 56 }]
+    }"#).unwrap();
+    assert_eq!(full.result.unwrap().status, "FILLED");
+    assert_eq!(full.fills.len(), 1);
+  }
+
+  #[test]
+  fn it_returns_parse_errors_instead_of_panicking() {
+    let result: Result<DepthResponse, SmartError> = parse_response("depth", r#"{ "lastUpdateId": 1, "bids": "oops" }"#);
+    match result {
+      Err(SmartError::ResponseParse { endpoint, body, .. }) => {
+        assert_eq!(endpoint, "depth");
+        assert!(body.contains("oops"));
+      },
+      _ => panic!("Expected a parse error")
+    }
+
+    let levels = vec![("1.5".to_string(), "not a number".to_string())];
+    assert!(matches!(parse_levels(&levels), Err(SmartError::ParseFloat(_))));
+  }
+}
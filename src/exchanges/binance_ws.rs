@@ -1,12 +1,12 @@
 // https://github.com/coderaidershaun/multithread-rust-arbitrage
-use crate::arb_detection::{validate_arbitrage_cycle, store_arb_cycle, calculate_arbitrage_surface_rate};
+use crate::arb_detection::{validate_arbitrage_cycle, store_arb_cycle, calculate_arbitrage_surface_rate, CycleCooldown};
 use crate::arb_execution::execute_arbitrage_cycle;
-use crate::bellmanford::BellmanFord;
-use crate::constants::{MIN_ARB_THRESH, ASSET_HOLDINGS, MODE};
-use crate::helpers::create_exchange_rates;
-use crate::models::{Mode, SmartError};
-use crate::traits::ApiCalls;
+use crate::bellmanford::{CycleKey, Edge};
+use crate::constants::{MIN_ARB_THRESH, ASSET_HOLDINGS, CYCLE_COOLDOWN_MS, IS_PAPER_TRADE, MAX_CYCLE_LENGTH, MODE, PAPER_BALANCES};
+use crate::incremental::IncrementalDetector;
+use crate::models::{BinanceConfig, ExchangeRate, Mode, SmartError};
 use super::binance::Binance;
+use super::paper::{paper_balance_sheet, PaperExchange};
 
 use tungstenite::{connect, Message};
 use std::collections::HashMap;
@@ -15,33 +15,53 @@ use url::Url;
 
 use std::time::Duration;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-
-const BINANCE_WS_API: &str = "wss://stream.binance.com:9443";
+use std::sync::{Arc, Mutex};
 
 /// Websocket Binance
 /// Listens to latest bid and ask prices for a set of assets
+/// Every tick re-scores only the candidate cycles trading that symbol
 pub async fn websocket_binance() -> Result<(), SmartError> {
+  websocket_binance_with_config(BinanceConfig::from_constants()).await
+}
+
+/// Websocket Binance With Config
+/// Runs the listener against the Binance environment in config, i.e. the testnet or a mock server
+pub async fn websocket_binance_with_config(config: BinanceConfig) -> Result<(), SmartError> {
 
   let tickers: Vec<&str> = vec!["BTCUSDT", "ETHUSDT", "LINKETH", "SOLETH", "SOLBTC", "LINKBTC"];
-  let is_calculating = Arc::new(AtomicBool::new(false));
-  let is_calculating_for_thread = is_calculating.clone();
+  let is_validating = Arc::new(AtomicBool::new(false));
+  let cooldown = Arc::new(Mutex::new(CycleCooldown::new(Duration::from_millis(CYCLE_COOLDOWN_MS))));
+  let paper_balances = paper_balance_sheet(&PAPER_BALANCES);
+
+  // Initialize Exchange, reconnects refresh it so the http client and rate limiter carry over
+  let mut exchange: Binance = Binance::unloaded(config);
 
   '_outer: loop {
 
-    // Initialize Exchange
-    let exchange: Binance = Binance::new().await;
-    let mut prices: HashMap<String, f64> = HashMap::new();
+    // Guard: Ensure exchange data is current before reconnecting
+    if let Err(e) = exchange.refresh().await {
+      eprintln!("Failed to refresh Binance exchange data: {}", e);
+      std::thread::sleep(Duration::from_millis(50));
+      continue '_outer;
+    }
+
+    // Initialize candidate cycles for the listener universe from the latest top of book
+    let book_tickers: HashMap<String, ExchangeRate> = exchange.book_tickers.iter()
+      .filter(|(symbol, _)| tickers.contains(&symbol.as_str()))
+      .map(|(symbol, book_ticker)| (symbol.clone(), book_ticker.clone()))
+      .collect();
+    let mut detector = IncrementalDetector::new(&book_tickers, &exchange.fees, &ASSET_HOLDINGS, MAX_CYCLE_LENGTH);
 
     // Construct Stream
     let ext_url: Vec<String> = tickers.iter().map(|t| format!("{}@bookTicker/", t.to_lowercase())).collect();
     let ext_url_str = ext_url.concat();
-    let mut binance_url = format!("{}/stream?streams={}", BINANCE_WS_API, ext_url_str);
+    let mut binance_url = format!("{}/stream?streams={}", exchange.config.ws_url, ext_url_str);
     binance_url.pop();
 
     // Connect to websocket
     let (mut socket, _) = connect(Url::parse(&binance_url).unwrap()).expect("Can't connect.");
     println!("thread: binance websocket running...");
+    let mut is_missed = false;
 
     'inner: loop {
       
@@ -60,93 +80,129 @@ pub async fn websocket_binance() -> Result<(), SmartError> {
 
       // Extract info
       let symbol: String = parsed_data["data"]["s"].as_str().unwrap().to_uppercase();
-      let best_ask: f64 = parsed_data["data"]["a"].as_str().unwrap().parse::<f64>().unwrap();
       let best_bid: f64 = parsed_data["data"]["b"].as_str().unwrap().parse::<f64>().unwrap();
+      let best_bid_size: f64 = parsed_data["data"]["B"].as_str().unwrap().parse::<f64>().unwrap();
+      let best_ask: f64 = parsed_data["data"]["a"].as_str().unwrap().parse::<f64>().unwrap();
+      let best_ask_size: f64 = parsed_data["data"]["A"].as_str().unwrap().parse::<f64>().unwrap();
 
-      // Insert price
-      let mid_price: f64 = (best_ask + best_bid) / 2.0;
-      prices.insert(symbol, mid_price);
+      // Re-score affected cycles
+      let Some(symbol_info) = exchange.symbols.get(&symbol) else { continue };
+      let book_ticker = ExchangeRate::from_book_ticker(symbol_info, best_bid, best_bid_size, best_ask, best_ask_size);
+      let updated_cycles: Vec<Vec<Edge>> = detector.update(&book_ticker);
+
+      // Guard: Ensure a profitable cycle, now or missed during the last validation
+      if updated_cycles.is_empty() && !is_missed {
+        continue;
+      }
 
-      // Guard: Continue processing messages if calculation is in progress
-      if is_calculating_for_thread.load(Ordering::Relaxed) {
+      // Guard: Ensure a previous opportunity is not still being validated or traded, remembering what was missed
+      if is_validating.swap(true, Ordering::Relaxed) {
+        is_missed |= !updated_cycles.is_empty();
         continue;
       }
 
-      // Update exchange rates
-      let exchange_rates: Vec<(String, String, f64)> = create_exchange_rates(&exchange.symbols, &prices);
+      // Cycles which turned profitable while validating are still held by the detector
+      let candidate_cycles = if is_missed { detector.profitable_cycles() } else { updated_cycles };
+      is_missed = false;
+      let cycles: Vec<Vec<Edge>> = {
+        let cooldown = cooldown.lock().unwrap();
+        candidate_cycles.into_iter()
+          .filter(|cycle| !cooldown.is_cooling_down(&CycleKey::from_cycle(cycle)))
+          .collect()
+      };
+
+      // Guard: Ensure a cycle is left to validate
+      if cycles.is_empty() {
+        is_validating.store(false, Ordering::Relaxed);
+        continue;
+      }
 
-      // Start arbitrage calculation on new thread
-      let is_calculating_clone = is_calculating.clone();
+      // Validate arbitrage on new thread
+      let is_validating_clone = is_validating.clone();
+      let cooldown_clone = cooldown.clone();
+      let paper_balances_clone = paper_balances.clone();
       let exch_clone = exchange.clone();
-      if prices.len() >= 3 {
-        tokio::spawn(async move {
-          is_calculating_clone.store(true, Ordering::Relaxed);
-
-          // Check for arbitrage
-          let bf: BellmanFord = BellmanFord::new(&exchange_rates);
-          let cycle_opt = bf.find_negative_cycle();
-          if let Some(cycle) = cycle_opt {
-            if cycle.len() > 0 {
-              let arb_opt = validate_arbitrage_cycle(&cycle, &exch_clone).await;
-              if let Some((arb_rate, symbols, directions, budget)) = arb_opt {
-
-                // Ensure arb rate
-                if arb_rate >= MIN_ARB_THRESH { 
-
-                  // Guard: Ensure from asset is ipart of Holding Assets
-                  let from_asset = cycle[0].from.as_str();
-                  if !ASSET_HOLDINGS.contains(&from_asset) { panic!("Error: Asset holdings do not include symbol") }
-
-                  // Execute and get store trigger
-                  let (is_store, is_trade) = match MODE {
-                    Mode::Listener(is_store, is_trade) => (is_store, is_trade),
-                    _ => (false, false)
-                  };
-
-                  // !!! PLACE TRADE !!!
-                  if is_trade {
-                    println!("Placing trade...");
-                    let result = execute_arbitrage_cycle(
-                      budget,
-                      &cycle,
-                      &symbols,
-                      &directions, 
-                      &exch_clone
-                    ).await;
-                    
-                    if let Err(e) = result {
-                      panic!("Failed to place trade: {:?}", e);
-                    }
-                  }
-
-                  // Store Result
-                  if is_store {
-                    let arb_surface: f64 = calculate_arbitrage_surface_rate(&cycle);
-                    let _: () = store_arb_cycle(&cycle, arb_rate, arb_surface).expect("Failed to save arb");
-                  }
+      tokio::spawn(async move {
+        for cycle in cycles {
+          let arb_opt = validate_arbitrage_cycle(&cycle, &exch_clone).await;
+          if let Some((arb_rate, budget)) = arb_opt {
+
+            // Ensure arb rate
+            if arb_rate >= MIN_ARB_THRESH { 
+
+              // Guard: Ensure from asset is ipart of Holding Assets
+              let from_asset = cycle[0].from.as_str();
+              if !ASSET_HOLDINGS.contains(&from_asset) { panic!("Error: Asset holdings do not include symbol") }
+              cooldown_clone.lock().unwrap().mark(CycleKey::from_cycle(&cycle));
+
+              // Execute and get store trigger
+              let (is_store, is_trade) = match MODE {
+                Mode::Listener(is_store, is_trade) => (is_store, is_trade),
+                _ => (false, false)
+              };
+
+              // !!! PLACE TRADE !!!
+              if is_trade {
+                println!("Placing trade...");
+                let result = if IS_PAPER_TRADE {
+                  let paper_exchange = PaperExchange::new(exch_clone.clone(), paper_balances_clone.clone());
+                  execute_arbitrage_cycle(budget, &cycle, &paper_exchange).await
+                } else {
+                  execute_arbitrage_cycle(budget, &cycle, &exch_clone).await
+                };
+                
+                // Guard: Only stop the bot when the account may be left in an unknown state
+                if let Err(e) = result {
+                  if e.is_fatal() { panic!("Failed to place trade: {:?}", e); }
+                  eprintln!("Trade stopped: {}", e);
                 }
               }
 
+              // Store Result
+              if is_store {
+                let arb_surface: f64 = calculate_arbitrage_surface_rate(&cycle);
+                let _: () = store_arb_cycle(&cycle, arb_rate, arb_surface).expect("Failed to save arb");
+              }
+
               // Sleep
               std::thread::sleep(Duration::from_millis(50));
+              break;
             }
           }
+        }
 
-          is_calculating_clone.store(false, Ordering::Relaxed);
-        });
-      }
+        is_validating_clone.store(false, Ordering::Relaxed);
+      });
     }
   }
 }
 
-// #[cfg(test)]
-// mod test {
-//   use super::*;
-
-//   #[tokio::test]
-//   async fn it_runs_binance_ws() {
-//     let init_symbols: Vec<String> = vec!["BTCUSDT".to_string(), "ETHUSDT".to_string(), "LINKUSDT".to_string()];
-//     let best_symbols = Arc::new(Mutex::new(init_symbols));
-//     let _ = websocket_binance(best_symbols).await;
-//   }
-// }
\ No newline at end of file
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::exchanges::mock_binance::MockBinance;
+
+  #[test]
+  fn it_runs_binance_ws_against_mock_server() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    mock.state.lock().unwrap().stream_messages = vec![
+      MockBinance::book_ticker_message("BTCUSDT", 39999.0, 1.0, 40001.0, 1.0),
+      MockBinance::book_ticker_message("SOLETH", 0.0531, 40.0, 0.0532, 40.0),
+    ];
+
+    // The listener never returns so it runs on its own runtime for the rest of the test process
+    let config = mock.config.clone();
+    std::thread::spawn(move || {
+      let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+      let _ = runtime.block_on(websocket_binance_with_config(config));
+    });
+
+    // A profitable SOLETH tick triggers validation against depth
+    assert!(mock.wait_for("/api/v3/depth?symbol=SOLETH", 1, Duration::from_secs(10)));
+    let streams = mock.state.lock().unwrap().streams.clone();
+    assert_eq!(streams.len(), 1);
+    assert!(streams[0].starts_with("/stream?streams=btcusdt@bookTicker/"));
+    assert!(streams[0].contains("soleth@bookTicker"));
+    assert!(mock.requests_to("/api/v3/order").is_empty());
+  }
+}
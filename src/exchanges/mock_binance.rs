@@ -0,0 +1,428 @@
+use crate::constants::SNAPSHOT_PATH;
+use crate::models::{BinanceConfig, OrderBook, SymbolInfo};
+use super::rate_limit::request_weight;
+use super::snapshot::BinanceSnapshot;
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// Mock State
+/// Scriptable market served by the mock, plus a log of everything the bot asked for
+/// Books come from the snapshot, fills are taken at the top of book unless a scripted fill is queued
+#[derive(Debug)]
+pub struct MockState {
+  pub snapshot: BinanceSnapshot,
+  pub balances: HashMap<String, f64>,
+  pub fills: VecDeque<Value>,
+  pub errors: HashMap<String, (u16, Value)>,
+  pub transient_errors: HashMap<String, VecDeque<(u16, Value)>>,
+  pub used_weight: u32,
+  pub retry_after_s: u64,
+  pub stream_messages: Vec<String>,
+  pub requests: Vec<String>,
+  pub orders: Vec<String>,
+  pub streams: Vec<String>
+}
+
+/// Mock Binance
+/// In-process HTTP and WebSocket server imitating the Binance endpoints the bot uses
+/// Point Binance at it with config, each server thread lives until the test process exits
+#[derive(Clone)]
+pub struct MockBinance {
+  pub config: BinanceConfig,
+  pub state: Arc<Mutex<MockState>>
+}
+
+impl MockBinance {
+  /// Start
+  /// Serves the recorded fixture snapshot with the given free balances
+  pub fn start(balances: &[(&str, f64)]) -> Self {
+    let snapshot = BinanceSnapshot::load(SNAPSHOT_PATH).expect("Failed to load Binance snapshot");
+    Self::start_with_snapshot(snapshot, balances)
+  }
+
+  pub fn start_with_snapshot(snapshot: BinanceSnapshot, balances: &[(&str, f64)]) -> Self {
+    let state = Arc::new(Mutex::new(MockState {
+      snapshot,
+      balances: balances.iter().map(|(asset, balance)| (asset.to_string(), *balance)).collect(),
+      fills: VecDeque::new(),
+      errors: HashMap::new(),
+      transient_errors: HashMap::new(),
+      used_weight: 0,
+      retry_after_s: 1,
+      stream_messages: vec![],
+      requests: vec![],
+      orders: vec![],
+      streams: vec![]
+    }));
+
+    let rest_listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock rest server");
+    let ws_listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock websocket server");
+    let config = BinanceConfig {
+      rest_url: format!("http://{}", rest_listener.local_addr().unwrap()),
+      ws_url: format!("ws://{}", ws_listener.local_addr().unwrap()),
+      api_key: Some("mock-api-key".to_string()),
+      api_secret: Some("mock-api-secret".to_string())
+    };
+
+    let rest_state = state.clone();
+    std::thread::spawn(move || {
+      for stream in rest_listener.incoming().flatten() {
+        let state = rest_state.clone();
+        std::thread::spawn(move || handle_http(stream, &state));
+      }
+    });
+
+    let ws_state = state.clone();
+    std::thread::spawn(move || {
+      for stream in ws_listener.incoming().flatten() {
+        let state = ws_state.clone();
+        std::thread::spawn(move || handle_websocket(stream, &state));
+      }
+    });
+
+    Self { config, state }
+  }
+
+  /// Book Ticker Message
+  /// Combined stream bookTicker message as sent by Binance
+  pub fn book_ticker_message(symbol: &str, bid: f64, bid_qty: f64, ask: f64, ask_qty: f64) -> String {
+    json!({
+      "stream": format!("{}@bookTicker", symbol.to_lowercase()),
+      "data": { "u": 1, "s": symbol, "b": bid.to_string(), "B": bid_qty.to_string(), "a": ask.to_string(), "A": ask_qty.to_string() }
+    }).to_string()
+  }
+
+  /// Order Response
+  /// FULL market order response as sent by Binance
+  pub fn order_response(symbol: &str, side: &str, status: &str, executed_qty: f64, quote_qty: f64) -> Value {
+    let price = if executed_qty > 0.0 { quote_qty / executed_qty } else { 0.0 };
+    json!({
+      "symbol": symbol,
+      "orderId": 1,
+      "orderListId": -1,
+      "clientOrderId": "mock",
+      "transactTime": 1,
+      "price": "0.00000000",
+      "origQty": executed_qty.to_string(),
+      "executedQty": executed_qty.to_string(),
+      "cummulativeQuoteQty": quote_qty.to_string(),
+      "status": status,
+      "timeInForce": "GTC",
+      "type": "MARKET",
+      "side": side,
+      "fills": [{ "price": price.to_string(), "qty": executed_qty.to_string(), "commission": "0", "commissionAsset": "BNB", "tradeId": 1 }]
+    })
+  }
+
+  /// Requests To
+  /// Logged requests whose path starts with path, i.e. "/api/v3/depth"
+  pub fn requests_to(&self, path: &str) -> Vec<String> {
+    let state = self.state.lock().unwrap();
+    state.requests.iter().filter(|request| request.split(' ').nth(1).is_some_and(|target| target.starts_with(path))).cloned().collect()
+  }
+
+  /// Wait For
+  /// Polls until at least count requests were made to path, giving up after timeout
+  pub fn wait_for(&self, path: &str, count: usize, timeout: std::time::Duration) -> bool {
+    let start = std::time::Instant::now();
+    while start.elapsed() < timeout {
+      if self.requests_to(path).len() >= count { return true; }
+      std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    false
+  }
+}
+
+/// Handle Http
+/// Serves one request per connection and closes it
+fn handle_http(stream: TcpStream, state: &Mutex<MockState>) {
+  let mut reader = BufReader::new(stream);
+  let mut request_line = String::new();
+  if reader.read_line(&mut request_line).is_err() { return; }
+  let request_line = request_line.trim().to_string();
+
+  let mut content_length = 0;
+  let mut api_key = None;
+  loop {
+    let mut header = String::new();
+    if reader.read_line(&mut header).is_err() { return; }
+    let header = header.trim();
+    if header.is_empty() { break; }
+    let Some((name, value)) = header.split_once(':') else { continue };
+    match name.trim().to_lowercase().as_str() {
+      "content-length" => content_length = value.trim().parse().unwrap_or(0),
+      "x-mbx-apikey" => api_key = Some(value.trim().to_string()),
+      _ => {}
+    }
+  }
+  let mut body = vec![0; content_length];
+  if reader.read_exact(&mut body).is_err() { return; }
+  let body = String::from_utf8_lossy(&body).to_string();
+
+  let (status, response, headers) = {
+    let mut state = state.lock().unwrap();
+    let (status, response) = route(&request_line, &body, api_key, &mut state);
+    let mut headers = format!("X-MBX-USED-WEIGHT-1M: {}\r\n", state.used_weight);
+    if status == 429 || status == 418 {
+      headers.push_str(&format!("Retry-After: {}\r\n", state.retry_after_s));
+    }
+    (status, response.to_string(), headers)
+  };
+  let reason = if status == 200 { "OK" } else { "Error" };
+  let mut stream = reader.into_inner();
+  let _ = write!(
+    stream,
+    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+    status, reason, response.len(), headers, response
+  );
+}
+
+/// Route
+/// Builds the response for a request from the scripted state
+fn route(request_line: &str, body: &str, api_key: Option<String>, state: &mut MockState) -> (u16, Value) {
+  state.requests.push(request_line.to_string());
+  let target = request_line.split(' ').nth(1).unwrap_or_default();
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+  state.used_weight += request_weight(path);
+
+  // Scripted errors win over everything, transient ones are served once each
+  if let Some((status, error)) = state.transient_errors.get_mut(path).and_then(VecDeque::pop_front) {
+    return (status, error);
+  }
+  if let Some((status, error)) = state.errors.get(path) {
+    return (*status, error.clone());
+  }
+
+  let is_signed = matches!(path, "/api/v3/order" | "/api/v3/account");
+  if is_signed && api_key.is_none() {
+    return (401, json!({ "code": -2014, "msg": "API-key format invalid." }));
+  }
+
+  match path {
+    "/api/v3/exchangeInfo" => (200, exchange_info(&state.snapshot)),
+    "/api/v3/ticker/price" => (200, Value::Array(state.snapshot.prices.iter()
+      .map(|(symbol, price)| json!({ "symbol": symbol, "price": price.to_string() }))
+      .collect())),
+    "/api/v3/ticker/bookTicker" => (200, Value::Array(state.snapshot.book_tickers.values()
+      .map(|book_ticker| json!({
+        "symbol": book_ticker.symbol,
+        "bidPrice": book_ticker.best_bid_price.to_string(),
+        "bidQty": book_ticker.best_bid_size.to_string(),
+        "askPrice": book_ticker.best_ask_price.to_string(),
+        "askQty": book_ticker.best_ask_size.to_string()
+      }))
+      .collect())),
+    "/api/v3/depth" => {
+      let params = parse_query(query);
+      match orderbook(&state.snapshot, params.get("symbol").map(String::as_str).unwrap_or_default()) {
+        Some(orderbook) => (200, json!({
+          "lastUpdateId": 1,
+          "bids": orderbook.bids.iter().map(|(price, qty)| [price.to_string(), qty.to_string()]).collect::<Vec<_>>(),
+          "asks": orderbook.asks.iter().map(|(price, qty)| [price.to_string(), qty.to_string()]).collect::<Vec<_>>()
+        })),
+        None => (400, json!({ "code": -1121, "msg": "Invalid symbol." }))
+      }
+    },
+    "/api/v3/order" => {
+      state.orders.push(body.to_string());
+      match state.fills.pop_front() {
+        Some(fill) => (200, fill),
+        None => fill_market_order(state, &parse_query(body))
+      }
+    },
+    "/api/v3/account" => (200, json!({
+      "balances": state.balances.iter()
+        .map(|(asset, free)| json!({ "asset": asset, "free": free.to_string(), "locked": "0.00000000" }))
+        .collect::<Vec<_>>()
+    })),
+    _ => (404, json!({ "code": -1000, "msg": "Unknown endpoint." }))
+  }
+}
+
+fn exchange_info(snapshot: &BinanceSnapshot) -> Value {
+  let symbols: Vec<Value> = snapshot.symbols.values().map(|symbol_info| json!({
+    "symbol": symbol_info.symbol,
+    "status": "TRADING",
+    "isSpotTradingAllowed": true,
+    "baseAsset": symbol_info.base_asset,
+    "quoteAsset": symbol_info.quote_asset,
+    "baseAssetPrecision": symbol_info.base_asset_precision,
+    "quoteAssetPrecision": symbol_info.quote_asset_precision,
+    "orderTypes": ["LIMIT", "LIMIT_MAKER", "MARKET", "STOP_LOSS_LIMIT", "TAKE_PROFIT_LIMIT"],
+    "quoteOrderQtyMarketAllowed": symbol_info.quote_order_qty_market_allowed,
+    "filters": symbol_filters(symbol_info)
+  })).collect();
+  json!({
+    "timezone": "UTC",
+    "rateLimits": [
+      { "rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 6000 },
+      { "rateLimitType": "ORDERS", "interval": "SECOND", "intervalNum": 10, "limit": 100 },
+      { "rateLimitType": "ORDERS", "interval": "DAY", "intervalNum": 1, "limit": 200000 }
+    ],
+    "symbols": symbols
+  })
+}
+
+/// Symbol Filters
+/// Exchange filters as Binance sends them, from the typed filters of the snapshot
+fn symbol_filters(symbol_info: &SymbolInfo) -> Vec<Value> {
+  let lot_size = &symbol_info.lot_size;
+  let notional = &symbol_info.notional;
+  let mut filters = vec![
+    json!({ "filterType": "LOT_SIZE", "minQty": lot_size.min_qty.to_string(), "maxQty": lot_size.max_qty.to_string(), "stepSize": lot_size.step_size.to_string() }),
+    json!({ "filterType": "ICEBERG_PARTS", "limit": 10 }),
+    json!({ "filterType": "TRAILING_DELTA", "minTrailingAboveDelta": 10, "maxTrailingAboveDelta": 2000, "minTrailingBelowDelta": 10, "maxTrailingBelowDelta": 2000 }),
+    json!({
+      "filterType": "NOTIONAL",
+      "minNotional": notional.min_notional.to_string(),
+      "applyMinToMarket": notional.apply_min_to_market,
+      "maxNotional": notional.max_notional.to_string(),
+      "applyMaxToMarket": notional.apply_max_to_market,
+      "avgPriceMins": 5
+    }),
+    json!({ "filterType": "MAX_NUM_ALGO_ORDERS", "maxNumAlgoOrders": 5 })
+  ];
+  if let Some(market_lot_size) = &symbol_info.market_lot_size {
+    filters.push(json!({
+      "filterType": "MARKET_LOT_SIZE",
+      "minQty": market_lot_size.min_qty.to_string(),
+      "maxQty": market_lot_size.max_qty.to_string(),
+      "stepSize": market_lot_size.step_size.to_string()
+    }));
+  }
+  if let Some(price_filter) = &symbol_info.price_filter {
+    filters.push(json!({
+      "filterType": "PRICE_FILTER",
+      "minPrice": price_filter.min_price.to_string(),
+      "maxPrice": price_filter.max_price.to_string(),
+      "tickSize": price_filter.tick_size.to_string()
+    }));
+  }
+  if let Some(percent_price) = &symbol_info.percent_price_by_side {
+    filters.push(json!({
+      "filterType": "PERCENT_PRICE_BY_SIDE",
+      "bidMultiplierUp": percent_price.bid_multiplier_up.to_string(),
+      "bidMultiplierDown": percent_price.bid_multiplier_down.to_string(),
+      "askMultiplierUp": percent_price.ask_multiplier_up.to_string(),
+      "askMultiplierDown": percent_price.ask_multiplier_down.to_string(),
+      "avgPriceMins": 5
+    }));
+  }
+  if let Some(max_num_orders) = symbol_info.max_num_orders {
+    filters.push(json!({ "filterType": "MAX_NUM_ORDERS", "maxNumOrders": max_num_orders }));
+  }
+  filters
+}
+
+/// Orderbook
+/// Recorded depth or a single level at the top of book
+fn orderbook(snapshot: &BinanceSnapshot, symbol: &str) -> Option<OrderBook> {
+  if let Some(orderbook) = snapshot.orderbooks.get(symbol) {
+    return Some(orderbook.clone());
+  }
+  let book_ticker = snapshot.book_tickers.get(symbol)?;
+  Some(OrderBook {
+    bids: vec![(book_ticker.best_bid_price, book_ticker.best_bid_size)],
+    asks: vec![(book_ticker.best_ask_price, book_ticker.best_ask_size)],
+    timestamp: 0
+  })
+}
+
+/// Fill Market Order
+/// SELL quantity into the best bid or BUY with quoteOrderQty from the best ask, moving balances
+fn fill_market_order(state: &mut MockState, params: &HashMap<String, String>) -> (u16, Value) {
+  let symbol = params.get("symbol").cloned().unwrap_or_default();
+  let Some(book_ticker) = state.snapshot.book_tickers.get(&symbol).cloned() else {
+    return (400, json!({ "code": -1121, "msg": "Invalid symbol." }));
+  };
+
+  let (base_qty, quote_qty) = match params.get("side").map(String::as_str) {
+    Some("SELL") => {
+      let quantity: f64 = params.get("quantity").and_then(|qty| qty.parse().ok()).unwrap_or_default();
+      (quantity, quantity * book_ticker.best_bid_price)
+    },
+    Some("BUY") => {
+      let quote_order_qty: f64 = params.get("quoteOrderQty").and_then(|qty| qty.parse().ok()).unwrap_or_default();
+      (quote_order_qty / book_ticker.best_ask_price, quote_order_qty)
+    },
+    _ => return (400, json!({ "code": -1102, "msg": "Mandatory parameter 'side' was not sent." }))
+  };
+
+  let (spent_asset, spent, received_asset, received) = if params.get("side").map(String::as_str) == Some("SELL") {
+    (&book_ticker.from, base_qty, &book_ticker.to, quote_qty)
+  } else {
+    (&book_ticker.to, quote_qty, &book_ticker.from, base_qty)
+  };
+  let free = state.balances.get(spent_asset).copied().unwrap_or_default();
+  if free < spent {
+    return (400, json!({ "code": -2010, "msg": "Account has insufficient balance for requested action." }));
+  }
+  state.balances.insert(spent_asset.clone(), free - spent);
+  *state.balances.entry(received_asset.clone()).or_default() += received;
+
+  let side = params.get("side").cloned().unwrap_or_default();
+  (200, MockBinance::order_response(&symbol, &side, "FILLED", base_qty, quote_qty))
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+  query.split('&')
+    .filter_map(|pair| pair.split_once('='))
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+/// Handle Websocket
+/// Sends every scripted stream message then holds the connection open
+#[allow(clippy::result_large_err)]
+fn handle_websocket(stream: TcpStream, state: &Mutex<MockState>) {
+  let mut uri = String::new();
+  let callback = |request: &tungstenite::handshake::server::Request, response| {
+    uri = request.uri().to_string();
+    Ok(response)
+  };
+  let Ok(mut socket) = tungstenite::accept_hdr(stream, callback) else { return };
+
+  let messages = {
+    let mut state = state.lock().unwrap();
+    state.streams.push(uri);
+    state.stream_messages.clone()
+  };
+  for message in messages {
+    if socket.write_message(tungstenite::Message::Text(message)).is_err() { return; }
+  }
+
+  // Closing would send the listener into its reconnect loop
+  loop { std::thread::park(); }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_scripts_errors_and_fills_market_orders_from_the_book() {
+    let mock = MockBinance::start(&[("USDT", 100.0)]);
+    let mut state = mock.state.lock().unwrap();
+
+    let params = parse_query("symbol=SOLUSDT&side=BUY&type=MARKET&quoteOrderQty=20");
+    let (status, fill) = fill_market_order(&mut state, &params);
+    assert_eq!(status, 200);
+    assert_eq!(fill["cummulativeQuoteQty"], "20");
+    assert!(state.balances["SOL"] > 0.19 && state.balances["SOL"] < 0.2);
+    assert_eq!(state.balances["USDT"], 80.0);
+
+    let (status, _) = fill_market_order(&mut state, &parse_query("symbol=SOLUSDT&side=BUY&quoteOrderQty=1000"));
+    assert_eq!(status, 400);
+
+    state.errors.insert("/api/v3/depth".to_string(), (429, json!({ "code": -1003, "msg": "Too many requests." })));
+    let (status, error) = route("GET /api/v3/depth?symbol=SOLUSDT HTTP/1.1", "", None, &mut state);
+    assert_eq!((status, error["code"].as_i64()), (429, Some(-1003)));
+
+    let (status, _) = route("GET /api/v3/account?timestamp=1 HTTP/1.1", "", None, &mut state);
+    assert_eq!(status, 401);
+  }
+}
@@ -1,2 +1,8 @@
 pub mod binance;
-pub mod binance_ws;
\ No newline at end of file
+pub mod binance_models;
+pub mod binance_ws;
+#[cfg(test)]
+pub mod mock_binance;
+pub mod paper;
+pub mod rate_limit;
+pub mod snapshot;
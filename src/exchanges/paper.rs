@@ -0,0 +1,184 @@
+use crate::constants::PAPER_BALANCES;
+use crate::helpers::{calculate_weighted_average_price, to_decimal};
+use crate::models::{BinanceError, DirectedRate, Direction, ExchangeRate, FeeSchedule, SmartError, SymbolInfo};
+use crate::traits::{ApiCalls, BellmanFordEx, ExchangeData};
+
+use async_trait::async_trait;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Paper Exchange
+/// Wraps live or recorded market data and simulates market orders by walking the current depth
+/// Fills are reported gross like Binance, the taker fee is taken from what is received on the virtual balance sheet
+#[derive(Debug, Clone)]
+pub struct PaperExchange<T> {
+  pub inner: T,
+  pub balances: Arc<Mutex<HashMap<String, f64>>>
+}
+
+impl<T> PaperExchange<T> {
+  /// New
+  /// Balances are shared so the same balance sheet can follow a refreshed inner exchange
+  pub fn new(inner: T, balances: Arc<Mutex<HashMap<String, f64>>>) -> Self {
+    Self { inner, balances }
+  }
+
+  pub fn with_balances(inner: T, balances: &[(&str, f64)]) -> Self {
+    Self::new(inner, paper_balance_sheet(balances))
+  }
+}
+
+/// Paper Balance Sheet
+/// Shared virtual balances, i.e. kept by the scanner across exchange refreshes
+pub fn paper_balance_sheet(balances: &[(&str, f64)]) -> Arc<Mutex<HashMap<String, f64>>> {
+  let balances = balances.iter().map(|(asset, balance)| (asset.to_string(), *balance)).collect();
+  Arc::new(Mutex::new(balances))
+}
+
+#[async_trait]
+impl<T> ApiCalls for PaperExchange<T>
+where T: ApiCalls + ExchangeData + Send + Sync
+{
+  async fn new() -> Self {
+    Self::with_balances(T::new().await, &PAPER_BALANCES)
+  }
+
+  async fn fetch_symbols(&self) -> Result<HashMap<String, SymbolInfo>, SmartError> {
+    self.inner.fetch_symbols().await
+  }
+
+  async fn fetch_prices(&self) -> Result<HashMap<String, f64>, SmartError> {
+    self.inner.fetch_prices().await
+  }
+
+  async fn fetch_book_tickers(&self, symbols: &HashMap<String, SymbolInfo>) -> Result<HashMap<String, ExchangeRate>, SmartError> {
+    self.inner.fetch_book_tickers(symbols).await
+  }
+
+  async fn get_orderbook_depth(&self, symbol: &str, direction: &Direction) -> Result<Vec<(f64, f64)>, SmartError> {
+    self.inner.get_orderbook_depth(symbol, direction).await
+  }
+
+  /// Place Market Order
+  /// Forward sells quantity of the base asset into bids, Reverse spends quantity of the quote asset on asks
+  /// FILLED when the book absorbs the whole order, EXPIRED with the partial amounts otherwise
+  /// The book walk and virtual balances are simulated in f64, amounts cross back as decimals like a Binance response
+  async fn place_market_order(&self, symbol: &str, direction: &Direction, quantity: Decimal) -> Result<(String, Decimal, Decimal), SmartError> {
+    let quantity = quantity.to_f64().ok_or(SmartError::Runtime(format!("Invalid quantity {}", quantity)))?;
+    let symbol_info = self.inner.symbols().get(symbol)
+      .ok_or(SmartError::Runtime(format!("Unknown symbol {}", symbol)))?
+      .clone();
+    let orderbook = self.inner.get_orderbook_depth(symbol, direction).await?;
+
+    // Walk the book
+    let (spent_asset, received_asset) = match direction {
+      Direction::Forward => (&symbol_info.base_asset, &symbol_info.quote_asset),
+      Direction::Reverse => (&symbol_info.quote_asset, &symbol_info.base_asset),
+    };
+    let (spent, received) = match calculate_weighted_average_price(&orderbook, quantity, direction) {
+      Some((_, total_cost, total_quantity)) => (total_cost, total_quantity),
+      None => (0.0, 0.0)
+    };
+    let status = if spent >= quantity * (1.0 - 1e-9) { "FILLED" } else { "EXPIRED" };
+
+    // Settle against the virtual balance sheet
+    {
+      let mut balances = self.balances.lock().unwrap();
+      let free = balances.get(spent_asset).copied().unwrap_or_default();
+      if free < quantity * (1.0 - 1e-9) {
+        return Err(SmartError::Binance(BinanceError {
+          path: "/api/v3/order".to_string(),
+          status: 400,
+          code: -2010,
+          msg: format!("Account has insufficient balance for requested action (paper {} {} < {})", spent_asset, free, quantity)
+        }));
+      }
+      let fee = self.inner.fees().taker_fee(symbol);
+      balances.insert(spent_asset.clone(), (free - spent).max(0.0));
+      *balances.entry(received_asset.clone()).or_default() += received * (1.0 - fee);
+    }
+
+    let (base_amount_out, quote_amount_out) = match direction {
+      Direction::Forward => (spent, received),
+      Direction::Reverse => (received, spent),
+    };
+    Ok((status.to_string(), to_decimal(base_amount_out), to_decimal(quote_amount_out)))
+  }
+
+  async fn get_asset_account_balance(&self, asset: &str) -> Result<Decimal, SmartError> {
+    Ok(to_decimal(self.balances.lock().unwrap().get(asset).copied().unwrap_or_default()))
+  }
+}
+
+impl<T: ExchangeData> BellmanFordEx for PaperExchange<T> {}
+
+impl<T: ExchangeData> ExchangeData for PaperExchange<T> {
+  fn symbols(&self) -> &HashMap<String, SymbolInfo> { self.inner.symbols() }
+  fn prices(&self) -> &HashMap<String, f64> { self.inner.prices() }
+  fn book_tickers(&self) -> &HashMap<String, ExchangeRate> { self.inner.book_tickers() }
+  fn exchange_rates(&self) -> &Vec<DirectedRate> { self.inner.exchange_rates() }
+  fn fees(&self) -> &FeeSchedule { self.inner.fees() }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::arb_execution::place_cycle_orders;
+  use crate::exchanges::snapshot::BinanceSnapshot;
+  use crate::models::BinanceErrorKind;
+
+  async fn balance<T: ApiCalls>(exchange: &T, asset: &str) -> f64 {
+    exchange.get_asset_account_balance(asset).await.unwrap().to_f64().unwrap()
+  }
+
+  #[tokio::test]
+  async fn it_walks_depth_and_charges_fees_on_paper() {
+    let exchange: PaperExchange<BinanceSnapshot> = PaperExchange::with_balances(BinanceSnapshot::new().await, &[("USDT", 100.0)]);
+    let asks = exchange.get_orderbook_depth("SOLUSDT", &Direction::Reverse).await.unwrap();
+
+    // Spend more than the first level holds
+    let budget = asks[0].0 * asks[0].1 * 1.5;
+    exchange.balances.lock().unwrap().insert("USDT".to_string(), budget);
+    let (status, base_qty, quote_qty) = exchange.place_market_order("SOLUSDT", &Direction::Reverse, to_decimal(budget)).await.unwrap();
+    let (base_qty, quote_qty) = (base_qty.to_f64().unwrap(), quote_qty.to_f64().unwrap());
+    assert_eq!(status, "FILLED");
+    assert!((quote_qty - budget).abs() < 1e-9);
+    assert!(base_qty < budget / asks[0].0 && base_qty > budget / asks[1].0);
+    assert!(balance(&exchange, "USDT").await.abs() < 1e-9);
+    assert!((balance(&exchange, "SOL").await - base_qty * 0.999).abs() < 1e-9);
+
+    // More than the whole book
+    let sol = balance(&exchange, "SOL").await;
+    exchange.balances.lock().unwrap().insert("SOL".to_string(), 1e6);
+    let (status, base_qty, _) = exchange.place_market_order("SOLUSDT", &Direction::Forward, Decimal::from(1_000_000)).await.unwrap();
+    assert_eq!(status, "EXPIRED");
+    assert!(base_qty > to_decimal(sol) && base_qty < Decimal::from(1_000_000));
+
+    // Balance sheet is enforced like Binance, without stopping the bot
+    match exchange.place_market_order("BTCUSDT", &Direction::Forward, Decimal::ONE).await {
+      Err(SmartError::Binance(error)) => {
+        assert_eq!(error.kind(), BinanceErrorKind::InsufficientBalance);
+        assert!(!SmartError::Binance(error).is_fatal());
+      },
+      result => panic!("Expected an insufficient balance error, got {:?}", result)
+    }
+  }
+
+  #[tokio::test]
+  async fn it_runs_full_execution_path_on_paper() {
+    let exchange: PaperExchange<BinanceSnapshot> = PaperExchange::with_balances(BinanceSnapshot::new().await, &[("USDT", 100.0)]);
+    let cycle = exchange.run_bellman_ford_bounded().into_iter()
+      .find(|cycle| cycle[0].from == "USDT")
+      .unwrap();
+
+    place_cycle_orders(25.0, &cycle, &exchange).await.unwrap();
+
+    let balances = exchange.balances.lock().unwrap();
+    assert!(balances["USDT"] > 100.0);
+    for leg in &cycle[1..] {
+      assert!(balances.contains_key(&leg.from));
+    }
+  }
+}
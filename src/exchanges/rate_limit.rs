@@ -0,0 +1,250 @@
+use crate::constants::{RATE_LIMIT_HEADROOM, RATE_LIMIT_MAX_WAIT_MS};
+use crate::models::SmartError;
+use super::binance_models::RateLimitResponse;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Rate Limit
+/// One entry of exchangeInfo.rateLimits and what has been used of it in the current window
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+  pub limit_type: String, // i.e. REQUEST_WEIGHT, ORDERS or RAW_REQUESTS
+  pub interval_ms: u64,
+  pub limit: u32,
+  pub header: Option<String>, // i.e. x-mbx-used-weight-1m, RAW_REQUESTS are not reported
+  pub window: u64,
+  pub used: u32
+}
+
+impl RateLimit {
+  pub fn new(limit_type: &str, interval: &str, interval_num: u64, limit: u32) -> Option<Self> {
+    let (unit_ms, unit) = match interval {
+      "SECOND" => (1_000, "s"),
+      "MINUTE" => (60_000, "m"),
+      "HOUR" => (3_600_000, "h"),
+      "DAY" => (86_400_000, "d"),
+      _ => return None
+    };
+    let header = match limit_type {
+      "REQUEST_WEIGHT" => Some(format!("x-mbx-used-weight-{}{}", interval_num, unit)),
+      "ORDERS" => Some(format!("x-mbx-order-count-{}{}", interval_num, unit)),
+      "RAW_REQUESTS" => None,
+      _ => return None
+    };
+    Some(Self { limit_type: limit_type.to_string(), interval_ms: unit_ms * interval_num.max(1), limit, header, window: 0, used: 0 })
+  }
+
+  /// Cost
+  /// How much of this limit a call uses
+  fn cost(&self, weight: u32, is_order: bool) -> u32 {
+    match self.limit_type.as_str() {
+      "REQUEST_WEIGHT" => weight,
+      "ORDERS" => is_order as u32,
+      _ => 1
+    }
+  }
+
+  /// Roll
+  /// Binance windows are aligned to the clock, a later window starts from zero
+  fn roll(&mut self, now: u64) {
+    let window = now / self.interval_ms;
+    if window != self.window {
+      self.window = window;
+      self.used = 0;
+    }
+  }
+
+  fn ms_to_next_window(&self, now: u64) -> u64 {
+    (self.window + 1) * self.interval_ms - now
+  }
+}
+
+/// Rate Limiter
+/// Governs Binance calls against the exchangeInfo limits, counting locally and syncing from the usage headers
+/// Calls over RATE_LIMIT_HEADROOM are held until the next window when it is near enough, otherwise rejected before Binance bans the ip
+#[derive(Debug)]
+pub struct RateLimiter {
+  limits: Mutex<Vec<RateLimit>>,
+  blocked_until: Mutex<u64>
+}
+
+impl Default for RateLimiter {
+  /// Default
+  /// Binance spot limits, used until exchangeInfo has been read
+  fn default() -> Self {
+    let limits = [
+      ("REQUEST_WEIGHT", "MINUTE", 1, 6000),
+      ("ORDERS", "SECOND", 10, 100),
+      ("ORDERS", "DAY", 1, 200000),
+      ("RAW_REQUESTS", "MINUTE", 5, 61000)
+    ];
+    Self::with_limits(limits.iter().filter_map(|(limit_type, interval, interval_num, limit)| RateLimit::new(limit_type, interval, *interval_num, *limit)).collect())
+  }
+}
+
+impl RateLimiter {
+  pub fn with_limits(limits: Vec<RateLimit>) -> Self {
+    Self { limits: Mutex::new(limits), blocked_until: Mutex::new(0) }
+  }
+
+  #[allow(dead_code)]
+  pub fn limits(&self) -> Vec<RateLimit> {
+    self.limits.lock().unwrap().clone()
+  }
+
+  /// Set Limits
+  /// Replaces the limits with exchangeInfo.rateLimits, keeping what was already used of matching limits
+  pub fn set_limits(&self, rate_limits: &[RateLimitResponse]) {
+    let mut limits: Vec<RateLimit> = rate_limits.iter()
+      .filter_map(|rate_limit| RateLimit::new(&rate_limit.rate_limit_type, &rate_limit.interval, rate_limit.interval_num, rate_limit.limit))
+      .collect();
+
+    // Guard: Ensure limits were returned
+    if limits.is_empty() { return; }
+
+    let mut current = self.limits.lock().unwrap();
+    for limit in limits.iter_mut() {
+      if let Some(existing) = current.iter().find(|existing| existing.limit_type == limit.limit_type && existing.interval_ms == limit.interval_ms) {
+        limit.window = existing.window;
+        limit.used = existing.used;
+      }
+    }
+    *current = limits;
+  }
+
+  /// Acquire
+  /// Waits until a call of weight fits every limit, or returns RateLimited when that would take longer than RATE_LIMIT_MAX_WAIT_MS
+  pub async fn acquire(&self, weight: u32, is_order: bool) -> Result<(), SmartError> {
+    loop {
+      match self.try_acquire(weight, is_order, now_ms()) {
+        0 => return Ok(()),
+        wait_ms if wait_ms <= RATE_LIMIT_MAX_WAIT_MS => tokio::time::sleep(Duration::from_millis(wait_ms)).await,
+        wait_ms => return Err(SmartError::RateLimited(Duration::from_millis(wait_ms)))
+      }
+    }
+  }
+
+  /// Try Acquire
+  /// Reserves the call and returns 0, or returns the ms until it would fit
+  fn try_acquire(&self, weight: u32, is_order: bool, now: u64) -> u64 {
+    let blocked_until = *self.blocked_until.lock().unwrap();
+    if blocked_until > now { return blocked_until - now; }
+
+    let mut limits = self.limits.lock().unwrap();
+    let mut wait_ms = 0;
+    for limit in limits.iter_mut() {
+      limit.roll(now);
+      let cost = limit.cost(weight, is_order);
+      if cost > 0 && (limit.used + cost) as f64 > limit.limit as f64 * RATE_LIMIT_HEADROOM {
+        wait_ms = wait_ms.max(limit.ms_to_next_window(now));
+      }
+    }
+
+    if wait_ms == 0 {
+      for limit in limits.iter_mut() {
+        limit.used += limit.cost(weight, is_order);
+      }
+    }
+    wait_ms
+  }
+
+  /// Update
+  /// Syncs usage from the response headers, a 429 or 418 blocks every call for Retry-After seconds
+  pub fn update(&self, status: StatusCode, headers: &HeaderMap) {
+    let now = now_ms();
+    for limit in self.limits.lock().unwrap().iter_mut() {
+      let Some(header) = &limit.header else { continue };
+      let Some(used) = headers.get(header.as_str()).and_then(|value| value.to_str().ok()?.parse::<u32>().ok()) else { continue };
+      limit.roll(now);
+      limit.used = limit.used.max(used); // in flight reservations are not in the header yet
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::IM_A_TEAPOT {
+      let retry_after_s = headers.get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok()?.parse::<u64>().ok())
+        .unwrap_or(60);
+      let mut blocked_until = self.blocked_until.lock().unwrap();
+      *blocked_until = (*blocked_until).max(now + retry_after_s * 1000);
+    }
+  }
+}
+
+/// Request Weight
+/// Binance weight of the endpoints the bot calls, i.e. depth at the default limit of 100
+pub fn request_weight(path: &str) -> u32 {
+  match path {
+    "/api/v3/exchangeInfo" => 20,
+    "/api/v3/account" => 20,
+    "/api/v3/depth" => 5,
+    "/api/v3/ticker/price" => 4,
+    "/api/v3/ticker/bookTicker" => 4,
+    _ => 1
+  }
+}
+
+fn now_ms() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use reqwest::header::HeaderValue;
+
+  #[test]
+  fn it_reads_exchange_info_limits() {
+    let limiter = RateLimiter::default();
+    let rate_limits: Vec<RateLimitResponse> = serde_json::from_value(serde_json::json!([
+      { "rateLimitType": "REQUEST_WEIGHT", "interval": "MINUTE", "intervalNum": 1, "limit": 1200 },
+      { "rateLimitType": "ORDERS", "interval": "SECOND", "intervalNum": 10, "limit": 50 },
+      { "rateLimitType": "UNKNOWN", "interval": "MINUTE", "intervalNum": 1, "limit": 1 }
+    ])).unwrap();
+    limiter.set_limits(&rate_limits);
+
+    let limits = limiter.limits();
+    assert_eq!(limits.len(), 2);
+    assert_eq!(limits[0].limit, 1200);
+    assert_eq!(limits[0].header.as_deref(), Some("x-mbx-used-weight-1m"));
+    assert_eq!(limits[1].interval_ms, 10_000);
+    assert_eq!(limits[1].header.as_deref(), Some("x-mbx-order-count-10s"));
+  }
+
+  #[test]
+  fn it_holds_calls_over_the_limit_until_the_next_window() {
+    let limiter = RateLimiter::with_limits(vec![
+      RateLimit::new("REQUEST_WEIGHT", "DAY", 1, 100).unwrap(),
+      RateLimit::new("ORDERS", "DAY", 1, 2).unwrap()
+    ]);
+    let now = 86_400_000 * 10 + 1000;
+
+    // Weight
+    assert_eq!(limiter.try_acquire(80, false, now), 0);
+    assert_eq!(limiter.try_acquire(20, false, now), 86_400_000 - 1000);
+    assert_eq!(limiter.try_acquire(20, false, now + 86_400_000), 0);
+
+    // Orders are counted apart from weight
+    assert_eq!(limiter.try_acquire(1, true, now + 86_400_000), 0);
+    assert!(limiter.try_acquire(1, true, now + 86_400_000) > 0);
+    assert_eq!(limiter.try_acquire(1, false, now + 86_400_000), 0);
+  }
+
+  #[tokio::test]
+  async fn it_syncs_headers_and_respects_retry_after() {
+    let limiter = RateLimiter::default();
+    let mut headers = HeaderMap::new();
+    headers.insert("X-MBX-USED-WEIGHT-1M", HeaderValue::from_static("5999"));
+    limiter.update(StatusCode::OK, &headers);
+    assert_eq!(limiter.limits()[0].used, 5999);
+
+    headers.clear();
+    headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+    limiter.update(StatusCode::IM_A_TEAPOT, &headers);
+    match limiter.acquire(1, false).await {
+      Err(SmartError::RateLimited(wait)) => assert!(wait > Duration::from_secs(100)),
+      _ => panic!("Expected the ban to be respected")
+    }
+  }
+}
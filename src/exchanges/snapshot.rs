@@ -0,0 +1,164 @@
+use crate::constants::SNAPSHOT_PATH;
+use crate::models::{DirectedRate, Direction, ExchangeRate, FeeSchedule, OrderBook, SmartError, SymbolInfo};
+use crate::traits::{ApiCalls, BellmanFordEx, ExchangeData};
+use crate::helpers;
+use super::binance::Binance;
+
+use async_trait::async_trait;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Binance Snapshot
+/// Everything Binance detection and validation reads, recorded to disk so it can run offline and deterministically
+/// Exchange rates are rebuilt from book tickers when a snapshot (i.e. a hand written fixture) leaves them out
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BinanceSnapshot {
+  pub symbols: HashMap<String, SymbolInfo>,
+  pub prices: HashMap<String, f64>,
+  pub book_tickers: HashMap<String, ExchangeRate>,
+  #[serde(default)]
+  pub exchange_rates: Vec<DirectedRate>,
+  pub fees: FeeSchedule,
+  #[serde(default)]
+  pub orderbooks: HashMap<String, OrderBook>,
+}
+
+impl BinanceSnapshot {
+  /// Load
+  /// Reads a snapshot saved with save or Binance::save_snapshot
+  #[allow(dead_code)]
+  pub fn load(path: &str) -> Result<Self, SmartError> {
+    let json = std::fs::read_to_string(path)?;
+    let mut snapshot: Self = serde_json::from_str(&json)?;
+    if snapshot.exchange_rates.is_empty() {
+      snapshot.exchange_rates = helpers::create_exchange_rates(&snapshot.book_tickers, &snapshot.fees);
+      snapshot.exchange_rates.sort_by(|a, b| a.symbol.cmp(&b.symbol)); // book tickers are unordered
+    }
+    Ok(snapshot)
+  }
+
+  #[allow(dead_code)]
+  pub fn save(&self, path: &str) -> Result<(), SmartError> {
+    std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+    Ok(())
+  }
+}
+
+impl From<&Binance> for BinanceSnapshot {
+  fn from(exchange: &Binance) -> Self {
+    Self {
+      symbols: exchange.symbols.clone(),
+      prices: exchange.prices.clone(),
+      book_tickers: exchange.book_tickers.clone(),
+      exchange_rates: exchange.exchange_rates.clone(),
+      fees: exchange.fees.clone(),
+      orderbooks: exchange.orderbooks.clone(),
+    }
+  }
+}
+
+#[async_trait]
+impl ApiCalls for BinanceSnapshot {
+  async fn new() -> Self {
+    Self::load(SNAPSHOT_PATH).expect("Failed to load Binance snapshot")
+  }
+
+  async fn fetch_symbols(&self) -> Result<HashMap<String, SymbolInfo>, SmartError> {
+    Ok(self.symbols.clone())
+  }
+
+  async fn fetch_prices(&self) -> Result<HashMap<String, f64>, SmartError> {
+    Ok(self.prices.clone())
+  }
+
+  async fn fetch_book_tickers(&self, symbols: &HashMap<String, SymbolInfo>) -> Result<HashMap<String, ExchangeRate>, SmartError> {
+    Ok(self.book_tickers.iter()
+      .filter(|(symbol, _)| symbols.contains_key(*symbol))
+      .map(|(symbol, book_ticker)| (symbol.clone(), book_ticker.clone()))
+      .collect())
+  }
+
+  /// Get Orderbook Depth
+  /// Returns the recorded orderbook, or a single level at the recorded top of book for symbols never sampled
+  async fn get_orderbook_depth(&self, symbol: &str, direction: &Direction) -> Result<Vec<(f64, f64)>, SmartError> {
+    if let Some(orderbook) = self.orderbooks.get(symbol) {
+      return match direction {
+        Direction::Forward => Ok(orderbook.bids.clone()),
+        Direction::Reverse => Ok(orderbook.asks.clone()),
+      };
+    }
+
+    let book_ticker = self.book_tickers.get(symbol)
+      .ok_or(SmartError::Runtime(format!("Symbol {} not in snapshot", symbol)))?;
+    match direction {
+      Direction::Forward => Ok(vec![(book_ticker.best_bid_price, book_ticker.best_bid_size)]),
+      Direction::Reverse => Ok(vec![(book_ticker.best_ask_price, book_ticker.best_ask_size)]),
+    }
+  }
+
+  async fn place_market_order(&self, _symbol: &str, _direction: &Direction, _quantity: Decimal) -> Result<(String, Decimal, Decimal), SmartError> {
+    Err(SmartError::Runtime("Orders cannot be placed against a snapshot".to_string()))
+  }
+
+  async fn get_asset_account_balance(&self, _asset: &str) -> Result<Decimal, SmartError> {
+    Err(SmartError::Runtime("No account balances in a snapshot".to_string()))
+  }
+}
+
+impl BellmanFordEx for BinanceSnapshot {}
+
+impl ExchangeData for BinanceSnapshot {
+  fn symbols(&self) -> &HashMap<String, SymbolInfo> { &self.symbols }
+  fn prices(&self) -> &HashMap<String, f64> { &self.prices }
+  fn book_tickers(&self) -> &HashMap<String, ExchangeRate> { &self.book_tickers }
+  fn exchange_rates(&self) -> &Vec<DirectedRate> { &self.exchange_rates }
+  fn fees(&self) -> &FeeSchedule { &self.fees }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::arb_detection::validate_arbitrage_cycle;
+  use crate::constants::MIN_ARB_THRESH;
+
+  #[tokio::test]
+  async fn it_loads_fixture_and_detects_offline() {
+    let exchange = BinanceSnapshot::new().await;
+    assert_eq!(exchange.exchange_rates.len(), exchange.book_tickers.len() * 2);
+
+    let cycles = exchange.run_bellman_ford_bounded();
+    assert!(!cycles.is_empty());
+    assert!(cycles.iter().all(|cycle| cycle.iter().any(|leg| leg.symbol == "SOLETH")));
+
+    // Deterministic: same input, same cycles
+    assert_eq!(cycles, BinanceSnapshot::new().await.run_bellman_ford_bounded());
+  }
+
+  #[tokio::test]
+  async fn it_validates_cycle_against_recorded_orderbooks() {
+    let exchange = BinanceSnapshot::new().await;
+    let cycle = exchange.run_bellman_ford_bounded().into_iter()
+      .find(|cycle| cycle[0].from == "USDT")
+      .unwrap();
+
+    let (real_rate, budget) = validate_arbitrage_cycle(&cycle, &exchange).await.unwrap();
+    assert!(real_rate > MIN_ARB_THRESH);
+    assert!(budget > 0.0);
+    assert!(exchange.place_market_order("SOLETH", &Direction::Forward, Decimal::ONE).await.is_err());
+  }
+
+  #[tokio::test]
+  async fn it_round_trips_a_snapshot_through_disk() {
+    let exchange = BinanceSnapshot::new().await;
+    let path = std::env::temp_dir().join("binance_snapshot_round_trip.json");
+    let path = path.to_str().unwrap();
+
+    exchange.save(path).unwrap();
+    let reloaded = BinanceSnapshot::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(reloaded.symbols.len(), exchange.symbols.len());
+    assert_eq!(reloaded.orderbooks.len(), exchange.orderbooks.len());
+    assert_eq!(reloaded.exchange_rates.len(), exchange.exchange_rates.len());
+  }
+}
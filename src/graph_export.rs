@@ -0,0 +1,170 @@
+use super::bellmanford::{BellmanFord, Edge};
+use super::models::{DirectedRate, Direction, SmartError};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// Graph Snapshot
+/// The rates a BellmanFord instance was built from together with the cycles detected on it
+/// Exports to GraphViz DOT, JSON and a CSV rate matrix, JSON and CSV reload as test fixtures
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GraphSnapshot {
+  pub rates: Vec<DirectedRate>,
+  pub cycles: Vec<Vec<Edge>>
+}
+
+#[allow(dead_code)]
+impl GraphSnapshot {
+  pub fn new(bf: &BellmanFord, cycles: &[Vec<Edge>]) -> Self {
+    Self { rates: bf.to_directed_rates(), cycles: cycles.to_vec() }
+  }
+
+  /// To Bellman Ford
+  /// Rebuilds the graph exactly as exported
+  pub fn to_bellman_ford(&self) -> BellmanFord {
+    BellmanFord::new(&self.rates)
+  }
+
+  /// To Dot
+  /// GraphViz digraph labelled with symbol and rate, edges and assets on a detected cycle drawn in red
+  pub fn to_dot(&self) -> String {
+    let cycle_edges: HashSet<(&str, &str, &str)> = self.cycles.iter()
+      .flatten()
+      .map(|edge| (edge.from.as_str(), edge.to.as_str(), edge.symbol.as_str()))
+      .collect();
+    let cycle_assets: BTreeSet<&str> = cycle_edges.iter().map(|&(from, _, _)| from).collect();
+
+    let mut dot = String::from("digraph exchange_rates {\n");
+    for asset in &cycle_assets {
+      let _ = writeln!(dot, "  \"{}\" [color=red, fontcolor=red];", asset);
+    }
+    for rate in &self.rates {
+      let is_cycle_edge = cycle_edges.contains(&(rate.from.as_str(), rate.to.as_str(), rate.symbol.as_str()));
+      let style = if is_cycle_edge { ", color=red, penwidth=2.5" } else { "" };
+      let _ = writeln!(dot, "  \"{}\" -> \"{}\" [label=\"{} {}\"{}];", rate.from, rate.to, rate.symbol, rate.rate, style);
+    }
+    dot.push_str("}\n");
+    dot
+  }
+
+  pub fn to_json(&self) -> Result<String, SmartError> {
+    Ok(serde_json::to_string_pretty(self)?)
+  }
+
+  pub fn from_json(json: &str) -> Result<Self, SmartError> {
+    Ok(serde_json::from_str(json)?)
+  }
+
+  /// To Csv Matrix
+  /// Square matrix of rates with from assets as rows and to assets as columns, empty where no market exists
+  /// Where two symbols join the same pair the better rate is kept
+  pub fn to_csv_matrix(&self) -> Result<String, SmartError> {
+    let assets: BTreeSet<&str> = self.rates.iter()
+      .flat_map(|rate| [rate.from.as_str(), rate.to.as_str()])
+      .collect();
+
+    let mut best_rates: HashMap<(&str, &str), f64> = HashMap::new();
+    for rate in &self.rates {
+      let best = best_rates.entry((rate.from.as_str(), rate.to.as_str())).or_insert(rate.rate);
+      *best = best.max(rate.rate);
+    }
+
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(std::iter::once("").chain(assets.iter().copied()))?;
+    for from in &assets {
+      let row: Vec<String> = assets.iter()
+        .map(|to| best_rates.get(&(*from, *to)).map(|rate| rate.to_string()).unwrap_or_default())
+        .collect();
+      wtr.write_record(std::iter::once(from.to_string()).chain(row))?;
+    }
+
+    let bytes = wtr.into_inner().map_err(|e| SmartError::Runtime(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| SmartError::Runtime(e.to_string()))
+  }
+
+  /// From Csv Matrix
+  /// Reloads a rate matrix, symbols are named from + to as the matrix does not record them
+  pub fn from_csv_matrix(matrix: &str) -> Result<Self, SmartError> {
+    let mut rdr = csv::Reader::from_reader(matrix.as_bytes());
+    let assets: Vec<String> = rdr.headers()?.iter().skip(1).map(|asset| asset.to_string()).collect();
+
+    let mut rates: Vec<DirectedRate> = Vec::new();
+    for record in rdr.records() {
+      let record = record?;
+      let from = record.get(0).unwrap_or_default().to_string();
+      for (to, cell) in assets.iter().zip(record.iter().skip(1)) {
+        if cell.is_empty() { continue; }
+        rates.push(DirectedRate {
+          from: from.clone(),
+          to: to.clone(),
+          rate: cell.parse::<f64>()?,
+          symbol: format!("{}{}", from, to),
+          direction: Direction::Forward
+        });
+      }
+    }
+
+    Ok(Self { rates, cycles: vec![] })
+  }
+
+  /// Save
+  /// Writes name.dot, name.json and name.csv into the given directory
+  pub fn save(&self, dir: &str, name: &str) -> Result<(), SmartError> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(format!("{}/{}.dot", dir, name), self.to_dot())?;
+    std::fs::write(format!("{}/{}.json", dir, name), self.to_json()?)?;
+    std::fs::write(format!("{}/{}.csv", dir, name), self.to_csv_matrix()?)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::bellmanford::CycleKey;
+
+  fn get_test_rate(from: &str, to: &str, rate: f64) -> DirectedRate {
+    DirectedRate { from: from.to_string(), to: to.to_string(), rate, symbol: format!("{}{}", from, to), direction: Direction::Forward }
+  }
+
+  fn get_test_snapshot() -> GraphSnapshot {
+    let bf = BellmanFord::new(&[
+      get_test_rate("USD", "EUR", 0.9),
+      get_test_rate("EUR", "USD", 1.21),
+      get_test_rate("USD", "GBP", 0.75),
+      get_test_rate("GBP", "USD", 1.33),
+      get_test_rate("GBP", "EUR", 1.197),
+    ]);
+    let cycles = bf.find_negative_cycle().into_iter().collect::<Vec<_>>();
+    GraphSnapshot::new(&bf, &cycles)
+  }
+
+  #[test]
+  fn it_reloads_json_and_csv_exports_as_fixtures() {
+    let snapshot = get_test_snapshot();
+    assert_eq!(snapshot.cycles.len(), 1);
+
+    let reloaded = GraphSnapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+    assert_eq!(reloaded.cycles.len(), snapshot.cycles.len());
+    for (reloaded_rate, rate) in reloaded.rates.iter().zip(&snapshot.rates) {
+      assert_eq!((&reloaded_rate.symbol, reloaded_rate.direction), (&rate.symbol, rate.direction));
+      assert!((reloaded_rate.rate - rate.rate).abs() < 1e-12);
+    }
+    let reloaded_cycle = reloaded.to_bellman_ford().find_negative_cycle().unwrap();
+    assert_eq!(CycleKey::from_cycle(&reloaded_cycle), CycleKey::from_cycle(&snapshot.cycles[0]));
+
+    let matrix = snapshot.to_csv_matrix().unwrap();
+    assert_eq!(matrix.lines().next().unwrap(), ",EUR,GBP,USD");
+    let reloaded = GraphSnapshot::from_csv_matrix(&matrix).unwrap();
+    assert_eq!(reloaded.rates.len(), snapshot.rates.len());
+    assert!(reloaded.to_bellman_ford().find_negative_cycle().is_some());
+  }
+
+  #[test]
+  fn it_highlights_cycles_in_dot() {
+    let snapshot = get_test_snapshot();
+    let dot = snapshot.to_dot();
+    let highlighted = dot.lines().filter(|line| line.contains("->") && line.contains("color=red")).count();
+    assert_eq!(highlighted, snapshot.cycles[0].len());
+    assert_eq!(dot.lines().filter(|line| line.contains("->")).count(), snapshot.rates.len());
+  }
+}
@@ -1,91 +1,443 @@
-use super::models::{Direction, SymbolInfo};
+use super::models::{DirectedRate, Direction, ExchangeRate, FeeSchedule, OrderBook, SymbolInfo};
+use rust_decimal::prelude::{FromPrimitive, RoundingStrategy};
+use rust_decimal::Decimal;
 use std::collections::HashMap;
 
 /// Create Exchange Rates
-/// Combines symbol and price information to create exchange rates
-pub fn create_exchange_rates(
-  symbols: &HashMap<String, SymbolInfo>,
-  prices: &HashMap<String, f64>
-) -> Vec<(String, String, f64)> {
+/// Builds directed rates from the top of book
+/// Base to quote sells into the best bid, quote to base buys from the best ask
+/// Rates are net of the taker fee so edge weights reflect what is actually received
+pub fn create_exchange_rates(book_tickers: &HashMap<String, ExchangeRate>, fees: &FeeSchedule) -> Vec<DirectedRate> {
   let mut exchange_rates = Vec::new();
-  for (symbol, symbol_info) in symbols {
-    if let Some(&rate) = prices.get(symbol) {
-      exchange_rates.push((symbol_info.base_asset.clone(), symbol_info.quote_asset.clone(), rate));
-      if rate != 0.0 {  // Prevent division by zero
-        exchange_rates.push((symbol_info.quote_asset.clone(), symbol_info.base_asset.clone(), 1.0 / rate));
+  for book_ticker in book_tickers.values() {
+    let (base_to_quote, quote_to_base) = create_directed_rates(book_ticker, fees);
+    if base_to_quote.rate > 0.0 {
+      exchange_rates.push(base_to_quote);
+    }
+    if quote_to_base.rate > 0.0 {
+      exchange_rates.push(quote_to_base);
+    }
+  }
+  exchange_rates
+}
+
+/// Create Directed Rates
+/// Base to quote (Forward) and quote to base (Reverse) rates for one symbol net of fees
+pub fn create_directed_rates(book_ticker: &ExchangeRate, fees: &FeeSchedule) -> (DirectedRate, DirectedRate) {
+  let (base_to_quote, quote_to_base) = create_net_book_rates(book_ticker, fees);
+  let forward = DirectedRate {
+    from: book_ticker.from.clone(),
+    to: book_ticker.to.clone(),
+    rate: base_to_quote,
+    symbol: book_ticker.symbol.clone(),
+    direction: Direction::Forward
+  };
+  let reverse = DirectedRate {
+    from: book_ticker.to.clone(),
+    to: book_ticker.from.clone(),
+    rate: quote_to_base,
+    symbol: book_ticker.symbol.clone(),
+    direction: Direction::Reverse
+  };
+  (forward, reverse)
+}
+
+/// Create Net Book Rates
+/// Base to quote and quote to base rates for one symbol net of fees (zero when a side is empty)
+pub fn create_net_book_rates(book_ticker: &ExchangeRate, fees: &FeeSchedule) -> (f64, f64) {
+  let base_to_quote = if book_ticker.best_bid_price > 0.0 {
+    fees.net_rate(&book_ticker.symbol, book_ticker.best_bid_price)
+  } else { 0.0 };
+  let quote_to_base = if book_ticker.best_ask_price > 0.0 {  // Prevent division by zero
+    fees.net_rate(&book_ticker.symbol, 1.0 / book_ticker.best_ask_price)
+  } else { 0.0 };
+  (base_to_quote, quote_to_base)
+}
+
+/// Create VWAP Exchange Rates
+/// Builds directed rates from cached orderbooks at the notional held in each from asset
+/// Falls back to the top of book where no orderbook or notional is available
+/// Edges whose book cannot absorb the notional are dropped
+pub fn create_vwap_exchange_rates(
+  book_tickers: &HashMap<String, ExchangeRate>,
+  orderbooks: &HashMap<String, OrderBook>,
+  notionals: &HashMap<String, f64>,
+  fees: &FeeSchedule
+) -> Vec<DirectedRate> {
+  let mut exchange_rates = Vec::new();
+  for book_ticker in book_tickers.values() {
+    let (mut base_to_quote, mut quote_to_base) = create_directed_rates(book_ticker, fees);
+
+    if let Some(orderbook) = orderbooks.get(&book_ticker.symbol) {
+      if let Some(&notional) = notionals.get(&base_to_quote.from) {
+        base_to_quote.rate = calculate_vwap_rate(&orderbook.bids, notional, &Direction::Forward)
+          .map(|rate| fees.net_rate(&book_ticker.symbol, rate))
+          .unwrap_or(0.0);
+      }
+      if let Some(&notional) = notionals.get(&quote_to_base.from) {
+        quote_to_base.rate = calculate_vwap_rate(&orderbook.asks, notional, &Direction::Reverse)
+          .map(|rate| fees.net_rate(&book_ticker.symbol, rate))
+          .unwrap_or(0.0);
       }
     }
+
+    if base_to_quote.rate > 0.0 {
+      exchange_rates.push(base_to_quote);
+    }
+    if quote_to_base.rate > 0.0 {
+      exchange_rates.push(quote_to_base);
+    }
   }
   exchange_rates
 }
 
+/// Calculate VWAP Rate
+/// Rate received from walking the orderbook with the full amount in (None if the book is too thin)
+pub fn calculate_vwap_rate(orderbook: &[(f64, f64)], amount_in: f64, direction: &Direction) -> Option<f64> {
+  let (weighted_average_price, total_cost, _) = calculate_weighted_average_price(orderbook, amount_in, direction)?;
+
+  // Guard: Ensure the book absorbed the full amount
+  if total_cost < amount_in * (1.0 - 1e-9) {
+    return None;
+  }
+
+  match direction {
+    Direction::Forward => Some(weighted_average_price),
+    Direction::Reverse => Some(1.0 / weighted_average_price),
+  }
+}
+
+/// Create Asset Notionals
+/// Converts a USD notional into an amount of each asset that has a USDT pair
+pub fn create_asset_notionals(
+  symbols: &HashMap<String, SymbolInfo>,
+  prices: &HashMap<String, f64>,
+  usd_notional: f64
+) -> HashMap<String, f64> {
+  let mut notionals = HashMap::new();
+  notionals.insert("USDT".to_string(), usd_notional);
+  for symbol_info in symbols.values() {
+    for asset in [&symbol_info.base_asset, &symbol_info.quote_asset] {
+      if notionals.contains_key(asset) { continue; }
+      if let Some(&price) = prices.get(&format!("{}USDT", asset)) {
+        if price > 0.0 { notionals.insert(asset.clone(), usd_notional / price); }
+      } else if let Some(&price) = prices.get(&format!("USDT{}", asset)) {
+        notionals.insert(asset.clone(), usd_notional * price);
+      }
+    }
+  }
+  notionals
+}
+
+/// Calculate Weighted Average Price
+/// Calculates the depth of the orderbook to get a real rate
+pub fn calculate_weighted_average_price(
+  orderbook: &[(f64, f64)],
+  budget: f64,
+  direction: &Direction,
+) -> Option<(f64, f64, f64)> {
+  let mut total_cost = 0.0;
+  let mut total_quantity = 0.0;
+
+  for &(price, quantity) in orderbook.iter() {
+
+    // Effective quantity is the amount of the quote asset you receive (or spend in reverse)
+    let effective_quantity = match direction {
+      Direction::Reverse => quantity,
+      Direction::Forward => quantity * price,
+    };
+
+    // Cost is the amount of the base asset you spend (or receive in forward)
+    let cost = match direction {
+      Direction::Reverse => quantity * price,
+      Direction::Forward => quantity,
+    };
+
+    // Check if adding this order exceeds the budget
+    if total_cost + cost > budget {
+      let remaining_budget = budget - total_cost;
+
+      // Adjust the remaining quantity based on the direction of the trade
+      let remaining_quantity = match direction {
+        Direction::Reverse => remaining_budget / price,
+        Direction::Forward => remaining_budget * price, // In forward, get quote asset amount
+      };
+
+      total_cost += remaining_budget;
+      total_quantity += remaining_quantity;
+      break;
+
+    } else {
+      total_cost += cost;
+      total_quantity += effective_quantity;
+    }
+
+    if total_cost >= budget {
+      break;
+    }
+  }
+
+  // Guard: Ensure quantity is not zero
+  if total_quantity == 0.0 {
+    return None;
+  }
+
+  // Weighted average price calculation
+  let weighted_average_price = match direction {
+    Direction::Reverse => total_cost / total_quantity,
+    Direction::Forward => total_quantity / total_cost,
+  };
+ 
+  Some((weighted_average_price, total_cost, total_quantity))
+}
+
+/// To Decimal
+/// Shortest decimal that round trips an f64, used where detection prices cross into order sizing
+pub fn to_decimal(value: f64) -> Decimal {
+  Decimal::from_f64(value).unwrap_or_default()
+}
+
 /// Validate Quantity
 /// Validates that the quantity being requested matches exchange criteria
-pub fn validate_quantity(symbol_info: &SymbolInfo, quantity: f64, general_price: f64, direction: &Direction) -> Result<f64, String> {
-  let min_qty: f64 = symbol_info.min_qty.parse().map_err(|_| "Invalid min_qty")?;
-  let max_qty: f64 = symbol_info.max_qty.parse().map_err(|_| "Invalid max_qty")?;
-  let step_size: f64 = symbol_info.step_size.parse().map_err(|_| "Invalid step_size")?;
-
-  let decimal_places: i32 = match direction {
-    Direction::Forward => symbol_info.base_asset_precision as i32,
-    Direction::Reverse => symbol_info.quote_asset_precision as i32,
-  };
+/// Checks every filter Binance applies to a market order so a leg is rejected here rather than by the exchange mid cycle
+/// book_price is the side of the book the order executes against, general_price the reference (average) price
+pub fn validate_quantity(
+  symbol_info: &SymbolInfo,
+  quantity: Decimal,
+  general_price: Decimal,
+  book_price: Decimal,
+  direction: &Direction
+) -> Result<Decimal, String> {
+  let lot_size = &symbol_info.lot_size;
+  let market_lot_size = symbol_info.market_lot_size.clone().unwrap_or_default();
+  let notional = &symbol_info.notional;
 
-  let mut quantity: f64 = quantity;
+  // Guard: Ensure prices are usable
+  if general_price <= Decimal::ZERO || book_price <= Decimal::ZERO {
+    return Err(format!("No price to size the order: {} {} {}", symbol_info.symbol, general_price, book_price));
+  }
 
-  // Ensure Precision: Check if the quantity aligns with the precision
-  let precision_factor = 10f64.powi(decimal_places);
-  quantity = (quantity * precision_factor).round() / precision_factor;
+  let decimal_places: u32 = match direction {
+    Direction::Forward => symbol_info.base_asset_precision as u32,
+    Direction::Reverse => symbol_info.quote_asset_precision as u32,
+  };
 
-  // Ensure Step Size: Check if the quantity aligns with the step size and adjust if necessary
+  // Ensure Precision: Round the quantity down to the asset precision so it never exceeds what is held
+  let mut quantity: Decimal = quantity.round_dp_with_strategy(decimal_places, RoundingStrategy::ToZero);
+
+  // Ensure Step Size: Truncate the quantity to the LOT_SIZE and MARKET_LOT_SIZE steps
   if direction == &Direction::Forward {
-    if (quantity / step_size).fract() > 0.0 {
-      quantity = (quantity / step_size).trunc() * step_size;
+    for step_size in [lot_size.step_size, market_lot_size.step_size] {
+      if step_size > Decimal::ZERO {
+        quantity = (quantity / step_size).trunc() * step_size;
+      }
     }
   }
-
-  // Format quantity to account for Rust f64 and f32 inherent imprecision
-  let formatted_quantity = format!("{:.*}", decimal_places as usize, quantity);
-  quantity = formatted_quantity.parse::<f64>().expect("Failed to parse formatted quantity back into f64");
+  quantity = quantity.normalize();
 
   // Guard: Ensure quantity remaining is not zero
-  if quantity == 0.0 {
-    return Err(format!("Effective quantity after trade would leave zero: {} {} {}", symbol_info.symbol, quantity * general_price, symbol_info.max_notional));
+  if quantity.is_zero() {
+    return Err(format!("Effective quantity after trade would leave zero: {} {} {}", symbol_info.symbol, quantity * general_price, notional.max_notional));
   }
 
-  // Checks for base asset
-  if direction == &Direction::Forward {
+  // Guard: Ensure market orders can be sized in the quote asset
+  if direction == &Direction::Reverse && !symbol_info.quote_order_qty_market_allowed {
+    return Err(format!("Market orders by quote quantity not allowed: {}", symbol_info.symbol));
+  }
 
-    // Guard: Check if the quantity is greater than or equal to the minimum size
-    if quantity < min_qty {
-      return Err(format!("Quantity is less than the minimum required: {} {} {}", symbol_info.symbol, quantity, min_qty));
+  // Guard: Ensure the book price used for sizing is a PRICE_FILTER price, within bounds and on a tick
+  if let Some(price_filter) = &symbol_info.price_filter {
+    if book_price < price_filter.min_price || (price_filter.max_price > Decimal::ZERO && book_price > price_filter.max_price) {
+      return Err(format!("Price outside price filter: {} {} {} {}", symbol_info.symbol, book_price, price_filter.min_price, price_filter.max_price));
     }
-
-    // Guard: Check if the quantity is less than or equal to the maximum
-    if quantity > max_qty {
-      return Err(format!("Quantity exceeds the maximum limit: {} {} {}", symbol_info.symbol, quantity, max_qty));
+    if price_filter.tick_size > Decimal::ZERO && !((book_price - price_filter.min_price) % price_filter.tick_size).is_zero() {
+      return Err(format!("Price not on price filter tick: {} {} {}", symbol_info.symbol, book_price, price_filter.tick_size));
     }
+  }
 
-    // Guard: Check if the quantity aligns with minimum notional value
-    if quantity * general_price < symbol_info.min_notional.parse().expect("Failed to parse min notional value") {
-      return Err(format!("Total trade value under minimum notional value: {} {} {}", symbol_info.symbol, quantity * general_price, symbol_info.min_notional));
+  // Guard: Ensure the book price is within PERCENT_PRICE_BY_SIDE of the reference price
+  if let Some(percent_price) = &symbol_info.percent_price_by_side {
+    let (multiplier_down, multiplier_up) = match direction {
+      Direction::Forward => (percent_price.bid_multiplier_down, percent_price.bid_multiplier_up),
+      Direction::Reverse => (percent_price.ask_multiplier_down, percent_price.ask_multiplier_up),
+    };
+    if book_price < general_price * multiplier_down || book_price > general_price * multiplier_up {
+      return Err(format!("Book price outside percent price by side: {} {} {}", symbol_info.symbol, book_price, general_price));
     }
+  }
 
-    // Guard: Check if the quantity aligns with minimum notional value
-    if quantity * general_price > symbol_info.max_notional.parse().expect("Failed to parse max notional value") {
-      return Err(format!("Total trade value over maximum notional value: {} {} {}", symbol_info.symbol, quantity * general_price, symbol_info.max_notional));
+  // Base quantity and quote value of the order, estimated from the book for quote sized orders
+  let (base_quantity, quote_value) = match direction {
+    Direction::Forward => (quantity, quantity * general_price),
+    Direction::Reverse => (quantity / book_price, quantity),
+  };
+
+  // Guard: Check base quantity against LOT_SIZE then MARKET_LOT_SIZE
+  for (filter, lot) in [("LOT_SIZE", lot_size), ("MARKET_LOT_SIZE", &market_lot_size)] {
+    if base_quantity < lot.min_qty {
+      return Err(format!("Quantity is less than the {} minimum: {} {} {}", filter, symbol_info.symbol, base_quantity, lot.min_qty));
     }
-  } else {
-    // Guard: Check if the quantity aligns with minimum notional value
-    if quantity < symbol_info.min_notional.parse().expect("Failed to parse min notional value") {
-      return Err(format!("Total trade value under minimum notional value: {} {} {}", symbol_info.symbol, quantity, symbol_info.min_notional));
+    if lot.max_qty > Decimal::ZERO && base_quantity > lot.max_qty {
+      return Err(format!("Quantity exceeds the {} maximum: {} {} {}", filter, symbol_info.symbol, base_quantity, lot.max_qty));
     }
+  }
 
-    // Guard: Check if the quantity aligns with minimum notional value
-    if quantity > symbol_info.max_notional.parse().expect("Failed to parse max notional value") {
-      return Err(format!("Total trade value over maximum notional value: {} {} {}", symbol_info.symbol, quantity, symbol_info.max_notional));
-    }
+  // Guard: Check if the quantity aligns with minimum notional value
+  if notional.apply_min_to_market && quote_value < notional.min_notional {
+    return Err(format!("Total trade value under minimum notional value: {} {} {}", symbol_info.symbol, quote_value, notional.min_notional));
+  }
+
+  // Guard: Check if the quantity aligns with maximum notional value
+  if notional.apply_max_to_market && notional.max_notional > Decimal::ZERO && quote_value > notional.max_notional {
+    return Err(format!("Total trade value over maximum notional value: {} {} {}", symbol_info.symbol, quote_value, notional.max_notional));
   }
 
   Ok(quantity)
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::models::{LotSizeFilter, NotionalFilter, PercentPriceBySideFilter, PriceFilter};
+  use rust_decimal_macros::dec;
+
+  fn get_test_symbol_info() -> SymbolInfo {
+    SymbolInfo {
+      symbol: "BTCUSDT".to_string(),
+      base_asset: "BTC".to_string(),
+      quote_asset: "USDT".to_string(),
+      base_asset_precision: 8,
+      quote_asset_precision: 8,
+      lot_size: LotSizeFilter { min_qty: dec!(0.00001000), max_qty: dec!(9000.00000000), step_size: dec!(0.00001000) },
+      market_lot_size: Some(LotSizeFilter { min_qty: dec!(0), max_qty: dec!(100.00000000), step_size: dec!(0) }),
+      notional: NotionalFilter { min_notional: dec!(5.00000000), apply_min_to_market: true, max_notional: dec!(9000000.00000000), apply_max_to_market: false },
+      price_filter: Some(PriceFilter { min_price: dec!(0.01000000), max_price: dec!(1000000.00000000), tick_size: dec!(0.01000000) }),
+      percent_price_by_side: Some(PercentPriceBySideFilter {
+        bid_multiplier_up: dec!(5), bid_multiplier_down: dec!(0.2), ask_multiplier_up: dec!(5), ask_multiplier_down: dec!(0.2)
+      }),
+      max_num_orders: Some(200),
+      quote_order_qty_market_allowed: true
+    }
+  }
+
+  #[test]
+  fn it_validates_quantity_against_every_market_filter() {
+    let mut symbol_info = get_test_symbol_info();
+    let price = dec!(40000);
+
+    // Step size and precision are exact
+    assert_eq!(validate_quantity(&symbol_info, dec!(0.0012345), price, price, &Direction::Forward), Ok(dec!(0.00123)));
+    assert_eq!(validate_quantity(&symbol_info, dec!(25), price, price, &Direction::Reverse), Ok(dec!(25)));
+
+    // NOTIONAL applies its minimum to market orders, the maximum only when flagged
+    assert!(validate_quantity(&symbol_info, dec!(0.0001), price, price, &Direction::Forward).is_err());
+    assert!(validate_quantity(&symbol_info, dec!(4), price, price, &Direction::Reverse).is_err());
+
+    // MARKET_LOT_SIZE is tighter than LOT_SIZE, also for quote sized orders
+    assert!(validate_quantity(&symbol_info, dec!(150), price, price, &Direction::Forward).unwrap_err().contains("MARKET_LOT_SIZE"));
+    assert!(validate_quantity(&symbol_info, dec!(150) * price, price, price, &Direction::Reverse).unwrap_err().contains("MARKET_LOT_SIZE"));
+
+    // PERCENT_PRICE_BY_SIDE and PRICE_FILTER bounds and tick on the book price
+    assert!(validate_quantity(&symbol_info, dec!(0.01), price, price * dec!(0.1), &Direction::Forward).unwrap_err().contains("percent price"));
+    assert!(validate_quantity(&symbol_info, dec!(0.01), dec!(0.001), dec!(0.001), &Direction::Forward).unwrap_err().contains("outside price filter"));
+    assert!(validate_quantity(&symbol_info, dec!(0.01), price, price + dec!(0.005), &Direction::Forward).unwrap_err().contains("tick"));
+    assert_eq!(validate_quantity(&symbol_info, dec!(0.01), price, price + dec!(0.01), &Direction::Forward), Ok(dec!(0.01)));
+
+    // Quote sized market orders must be allowed
+    symbol_info.quote_order_qty_market_allowed = false;
+    assert!(validate_quantity(&symbol_info, dec!(25), price, price, &Direction::Reverse).is_err());
+  }
+
+  #[test]
+  fn it_truncates_to_step_size_without_float_artefacts() {
+    let mut symbol_info = get_test_symbol_info();
+    symbol_info.lot_size.step_size = dec!(0.1);
+    symbol_info.market_lot_size = None;
+    symbol_info.percent_price_by_side = None;
+
+    // 0.7 / 0.1 is 6.999999999999999 in f64, which truncated a whole step away
+    let validated = validate_quantity(&symbol_info, dec!(0.7), dec!(100), dec!(100), &Direction::Forward).unwrap();
+    assert_eq!(validated.to_string(), "0.7");
+    let validated = validate_quantity(&symbol_info, to_decimal(0.1) + to_decimal(0.2), dec!(100), dec!(100), &Direction::Forward).unwrap();
+    assert_eq!(validated.to_string(), "0.3");
+
+    // Exactly on a step is left alone, just below truncates
+    symbol_info.lot_size.step_size = dec!(0.00001);
+    assert_eq!(validate_quantity(&symbol_info, dec!(0.07), dec!(100), dec!(100), &Direction::Forward).unwrap().to_string(), "0.07");
+    assert_eq!(validate_quantity(&symbol_info, dec!(0.0699999), dec!(100), dec!(100), &Direction::Forward).unwrap().to_string(), "0.06999");
+    assert_eq!(to_decimal(0.007499625).to_string(), "0.007499625");
+
+    // Quote sized orders skip step truncation, precision rounding never sizes above the balance
+    let balance = dec!(25.123456789);
+    let validated = validate_quantity(&symbol_info, balance, dec!(100), dec!(100), &Direction::Reverse).unwrap();
+    assert_eq!(validated, dec!(25.12345678));
+    assert!(validated <= balance);
+  }
+
+  #[test]
+  fn it_creates_exchange_rates_from_bid_and_ask() {
+    let symbol_info = get_test_symbol_info();
+    let mut book_tickers = HashMap::new();
+    book_tickers.insert("BTCUSDT".to_string(), ExchangeRate::from_book_ticker(&symbol_info, 40000.0, 1.5, 40010.0, 2.0));
+
+    let fees = FeeSchedule { default_taker_fee: 0.0, overrides: HashMap::new(), is_bnb_discount: false };
+    let exchange_rates = create_exchange_rates(&book_tickers, &fees);
+    assert_eq!(exchange_rates.len(), 2);
+    let sell = exchange_rates.iter().find(|rate| rate.direction == Direction::Forward).unwrap();
+    let buy = exchange_rates.iter().find(|rate| rate.direction == Direction::Reverse).unwrap();
+    assert_eq!((sell.from.as_str(), sell.to.as_str(), sell.rate), ("BTC", "USDT", 40000.0));
+    assert_eq!((buy.from.as_str(), buy.to.as_str(), buy.rate), ("USDT", "BTC", 1.0 / 40010.0));
+    assert_eq!(sell.symbol, "BTCUSDT");
+
+    // Round trip through the spread always loses
+    let round_trip: f64 = exchange_rates.iter().map(|rate| rate.rate).product();
+    assert!(round_trip < 1.0);
+  }
+
+  #[test]
+  fn it_creates_vwap_exchange_rates_at_notional() {
+    let symbol_info = get_test_symbol_info();
+    let mut book_tickers = HashMap::new();
+    book_tickers.insert("BTCUSDT".to_string(), ExchangeRate::from_book_ticker(&symbol_info, 40000.0, 0.1, 40010.0, 0.1));
+
+    let mut orderbooks = HashMap::new();
+    orderbooks.insert("BTCUSDT".to_string(), OrderBook {
+      bids: vec![(40000.0, 0.1), (39900.0, 1.0)],
+      asks: vec![(40010.0, 0.1), (40110.0, 1.0)],
+      timestamp: 0
+    });
+
+    let fees = FeeSchedule { default_taker_fee: 0.0, overrides: HashMap::new(), is_bnb_discount: false };
+    let mut notionals = HashMap::new();
+    notionals.insert("BTC".to_string(), 0.2);
+    notionals.insert("USDT".to_string(), 8000.0);
+
+    let exchange_rates = create_vwap_exchange_rates(&book_tickers, &orderbooks, &notionals, &fees);
+    let sell = exchange_rates.iter().find(|rate| rate.direction == Direction::Forward).unwrap();
+    let buy = exchange_rates.iter().find(|rate| rate.direction == Direction::Reverse).unwrap();
+    assert!((sell.rate - 39950.0).abs() < 1e-6);
+    assert!(buy.rate < 1.0 / 40010.0);
+
+    // Notional larger than the book removes the edge
+    notionals.insert("BTC".to_string(), 5.0);
+    let exchange_rates = create_vwap_exchange_rates(&book_tickers, &orderbooks, &notionals, &fees);
+    assert!(exchange_rates.iter().all(|rate| rate.direction == Direction::Reverse));
+  }
+
+  #[test]
+  fn it_nets_taker_fees_out_of_exchange_rates() {
+    let symbol_info = get_test_symbol_info();
+    let mut book_tickers = HashMap::new();
+    book_tickers.insert("BTCUSDT".to_string(), ExchangeRate::from_book_ticker(&symbol_info, 40000.0, 1.5, 40000.0, 2.0));
+
+    let mut overrides = HashMap::new();
+    overrides.insert("BTCUSDT".to_string(), 0.002);
+    let fees = FeeSchedule { default_taker_fee: 0.001, overrides, is_bnb_discount: true };
+    assert!((fees.taker_fee("BTCUSDT") - 0.0015).abs() < 1e-12);
+    assert!((fees.taker_fee("ETHUSDT") - 0.00075).abs() < 1e-12);
+
+    let exchange_rates = create_exchange_rates(&book_tickers, &fees);
+    let sell_rate = exchange_rates.iter().find(|rate| rate.from == "BTC").unwrap().rate;
+    assert!((sell_rate - 40000.0 * (1.0 - 0.0015)).abs() < 1e-9);
+  }
+}
@@ -0,0 +1,139 @@
+use super::bellmanford::{BellmanFord, Edge};
+use super::helpers::{create_directed_rates, create_net_book_rates};
+use super::models::{DirectedRate, ExchangeRate, FeeSchedule};
+use std::collections::HashMap;
+
+/// Incremental Detector
+/// Holds every candidate cycle for a fixed universe with an index from symbol to the cycles using it
+/// A single book ticker update only re-scores the cycles that trade that symbol
+#[derive(Debug)]
+pub struct IncrementalDetector {
+  pub bf: BellmanFord,
+  pub fees: FeeSchedule,
+  pub cycles: Vec<Vec<usize>>,
+  pub cycle_weights: Vec<f64>,
+  pub symbol_edges: HashMap<String, (usize, usize)>,
+  pub symbol_cycles: HashMap<String, Vec<usize>>
+}
+
+impl IncrementalDetector {
+  pub fn new(book_tickers: &HashMap<String, ExchangeRate>, fees: &FeeSchedule, sources: &[&str], max_length: usize) -> Self {
+    let mut exchange_rates: Vec<DirectedRate> = Vec::new();
+    let mut symbol_edges: HashMap<String, (usize, usize)> = HashMap::new();
+
+    // Both directions are always added so an empty side can come back to life on a later update
+    for book_ticker in book_tickers.values() {
+      let (base_to_quote, quote_to_base) = create_directed_rates(book_ticker, fees);
+      exchange_rates.push(base_to_quote);
+      exchange_rates.push(quote_to_base);
+      symbol_edges.insert(book_ticker.symbol.clone(), (exchange_rates.len() - 2, exchange_rates.len() - 1));
+    }
+
+    let bf = BellmanFord::new(&exchange_rates);
+    let cycles = bf.enumerate_cycle_indices(sources, max_length);
+    let cycle_weights = cycles.iter().map(|cycle| bf.cycle_weight(cycle)).collect();
+
+    // Index cycles by every symbol they trade
+    let mut symbol_cycles: HashMap<String, Vec<usize>> = HashMap::new();
+    for (cycle_index, cycle) in cycles.iter().enumerate() {
+      for &edge_index in cycle {
+        let symbol = &bf.symbols[bf.edges[edge_index].symbol];
+        let cycle_indices = symbol_cycles.entry(symbol.clone()).or_default();
+        if !cycle_indices.contains(&cycle_index) {
+          cycle_indices.push(cycle_index);
+        }
+      }
+    }
+
+    Self { bf, fees: fees.clone(), cycles, cycle_weights, symbol_edges, symbol_cycles }
+  }
+
+  /// Update
+  /// Applies a book ticker update and returns the affected cycles which are now profitable, best first
+  pub fn update(&mut self, book_ticker: &ExchangeRate) -> Vec<Vec<Edge>> {
+
+    // Guard: Ensure symbol is part of the universe
+    let Some(&(base_to_quote_edge, quote_to_base_edge)) = self.symbol_edges.get(&book_ticker.symbol) else { return vec![] };
+
+    // Update edge weights
+    let (base_to_quote, quote_to_base) = create_net_book_rates(book_ticker, &self.fees);
+    self.bf.edges[base_to_quote_edge].weight = -f64::log10(base_to_quote);
+    self.bf.edges[quote_to_base_edge].weight = -f64::log10(quote_to_base);
+
+    // Guard: Ensure symbol is part of a candidate cycle
+    let Some(cycle_indices) = self.symbol_cycles.get(&book_ticker.symbol) else { return vec![] };
+
+    // Re-score affected cycles only
+    let mut profitable: Vec<(f64, usize)> = Vec::new();
+    for &cycle_index in cycle_indices {
+      let weight = self.bf.cycle_weight(&self.cycles[cycle_index]);
+      self.cycle_weights[cycle_index] = weight;
+      if weight < 0.0 {
+        profitable.push((weight, cycle_index));
+      }
+    }
+
+    profitable.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    profitable.iter().map(|&(_, cycle_index)| self.bf.to_named_cycle(&self.cycles[cycle_index])).collect()
+  }
+
+  /// Profitable Cycles
+  /// Every candidate cycle which is profitable at the last scored weights, best first
+  pub fn profitable_cycles(&self) -> Vec<Vec<Edge>> {
+    let mut profitable: Vec<(f64, usize)> = self.cycle_weights.iter().enumerate()
+      .filter(|(_, &weight)| weight < 0.0)
+      .map(|(cycle_index, &weight)| (weight, cycle_index))
+      .collect();
+
+    profitable.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    profitable.iter().map(|&(_, cycle_index)| self.bf.to_named_cycle(&self.cycles[cycle_index])).collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn get_test_book_ticker(symbol: &str, base: &str, quote: &str, price: f64) -> ExchangeRate {
+    ExchangeRate {
+      symbol: symbol.to_string(),
+      from: base.to_string(),
+      to: quote.to_string(),
+      best_bid_price: price,
+      best_ask_price: price,
+      best_bid_size: 1.0,
+      best_ask_size: 1.0
+    }
+  }
+
+  #[test]
+  fn it_rescores_only_cycles_using_updated_symbol() {
+    let mut book_tickers = HashMap::new();
+    book_tickers.insert("BTCUSDT".to_string(), get_test_book_ticker("BTCUSDT", "BTC", "USDT", 40000.0));
+    book_tickers.insert("ETHUSDT".to_string(), get_test_book_ticker("ETHUSDT", "ETH", "USDT", 2000.0));
+    book_tickers.insert("ETHBTC".to_string(), get_test_book_ticker("ETHBTC", "ETH", "BTC", 0.05));
+    book_tickers.insert("SOLBNB".to_string(), get_test_book_ticker("SOLBNB", "SOL", "BNB", 0.2));
+    let fees = FeeSchedule { default_taker_fee: 0.0, overrides: HashMap::new(), is_bnb_discount: false };
+
+    let mut detector = IncrementalDetector::new(&book_tickers, &fees, &["USDT"], 3);
+    assert_eq!(detector.cycles.len(), 2);
+    assert!(!detector.symbol_cycles.contains_key("SOLBNB"));
+
+    // Unrelated and unprofitable updates
+    assert!(detector.update(&get_test_book_ticker("SOLBNB", "SOL", "BNB", 0.3)).is_empty());
+    assert!(detector.update(&get_test_book_ticker("ETHBTC", "ETH", "BTC", 0.05)).is_empty());
+
+    // Cheap ETH in BTC terms: USDT -> BTC -> ETH -> USDT
+    let cycles = detector.update(&get_test_book_ticker("ETHBTC", "ETH", "BTC", 0.049));
+    assert_eq!(cycles.len(), 1);
+    let path: Vec<&str> = cycles[0].iter().map(|edge| edge.from.as_str()).collect();
+    assert_eq!(path, vec!["USDT", "BTC", "ETH"]);
+
+    // Cached weights keep the cycle profitable until it is re-scored
+    assert_eq!(detector.profitable_cycles(), cycles);
+    detector.update(&get_test_book_ticker("SOLBNB", "SOL", "BNB", 0.2));
+    assert_eq!(detector.profitable_cycles().len(), 1);
+    detector.update(&get_test_book_ticker("ETHBTC", "ETH", "BTC", 0.05));
+    assert!(detector.profitable_cycles().is_empty());
+  }
+}
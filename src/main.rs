@@ -3,7 +3,9 @@ mod arb_execution;
 mod bellmanford;
 mod constants;
 mod exchanges;
+mod graph_export;
 mod helpers;
+mod incremental;
 mod models;
 mod traits;
 
@@ -1,21 +1,118 @@
+use super::constants::{BINANCE_ENVIRONMENT, BNB_FEE_DISCOUNT, IS_BNB_FEE_DISCOUNT, TAKER_FEE, TAKER_FEE_OVERRIDES};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
 #[derive(thiserror::Error, Debug)]
 pub enum SmartError {
   #[error("Runtime error check failed")]
   Runtime(String),
+  #[error("Failed to parse {endpoint} response: {source}")]
+  ResponseParse { endpoint: String, body: String, source: serde_json::Error },
+  #[error("Rate limited by Binance, retry after {0:?}")]
+  RateLimited(std::time::Duration),
+  #[error("Binance error {} (http {}): {}", .0.code, .0.status, .0.msg)]
+  Binance(BinanceError),
+  #[error("Order rejected before sending: {0}")]
+  Validation(String),
+  #[error("Cycle stopped at leg {leg} holding {asset}: {source}")]
+  CycleIncomplete { leg: usize, asset: String, source: Box<SmartError> },
   #[error(transparent)]
   Csv(#[from] csv::Error),
   #[error(transparent)]
   Reqwest(#[from] reqwest::Error),
   #[error(transparent)]
-  Websocket(#[from] tungstenite::Error),
+  Websocket(#[from] Box<tungstenite::Error>),
   #[error(transparent)]
   ParseFloat(#[from] std::num::ParseFloatError),
   #[error(transparent)]
+  ParseDecimal(#[from] rust_decimal::Error),
+  #[error(transparent)]
   Io(#[from] std::io::Error),
   #[error(transparent)]
   SerdeJson(#[from] serde_json::Error)
 }
 
+impl SmartError {
+  /// Is Fatal
+  /// Errors the bot should not keep trading through, i.e. bad credentials or an order in an unknown state
+  /// Rate limits, exchange rejections and locally rejected orders only stop the current cycle unless a leg already filled
+  pub fn is_fatal(&self) -> bool {
+    match self {
+      Self::Binance(error) => error.kind().is_fatal(),
+      Self::RateLimited(_) | Self::Validation(_) => false,
+      _ => true
+    }
+  }
+}
+
+/// Binance Error
+/// Error body Binance returns with every rejected request, code 0 when the body was not a Binance error
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinanceError {
+  pub path: String, // i.e. /api/v3/order
+  pub status: u16,
+  pub code: i64,
+  pub msg: String
+}
+
+/// Binance Error Kind
+/// Binance error codes grouped by what the caller can do about them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinanceErrorKind {
+  FilterFailure,
+  InsufficientBalance,
+  OrderRejected,
+  Timestamp,
+  RateLimited,
+  Unavailable,
+  ExecutionUnknown,
+  Unauthorized,
+  InvalidRequest
+}
+
+impl BinanceErrorKind {
+  /// Is Retryable
+  /// The same request can be sent again (re-signed) without risking a double fill
+  pub fn is_retryable(&self) -> bool {
+    matches!(self, Self::Timestamp | Self::RateLimited | Self::Unavailable)
+  }
+
+  /// Is Fatal
+  /// Needs a human, i.e. the api key is rejected or an order may or may not have executed
+  pub fn is_fatal(&self) -> bool {
+    matches!(self, Self::Unauthorized | Self::ExecutionUnknown)
+  }
+}
+
+impl BinanceError {
+  /// Kind
+  /// Maps the Binance code, falling back on the http status for bodies without one
+  /// A 5xx on an order is ExecutionUnknown whatever its code, Binance documents its execution status as unknown
+  pub fn kind(&self) -> BinanceErrorKind {
+    let is_order = self.path == "/api/v3/order";
+    if is_order && self.status >= 500 { return BinanceErrorKind::ExecutionUnknown; }
+    let kind = match self.code {
+      -1013 | -1111 | -1112 => BinanceErrorKind::FilterFailure,
+      -2010 if self.msg.to_lowercase().contains("insufficient balance") => BinanceErrorKind::InsufficientBalance,
+      -2010 | -2011 => BinanceErrorKind::OrderRejected,
+      -1021 => BinanceErrorKind::Timestamp,
+      -1003 | -1015 => BinanceErrorKind::RateLimited,
+      -1001 | -1008 | -1016 => BinanceErrorKind::Unavailable,
+      -1000 | -1006 | -1007 => BinanceErrorKind::ExecutionUnknown,
+      -1002 | -1022 | -2014 | -2015 => BinanceErrorKind::Unauthorized,
+      _ => match self.status {
+        401 | 403 => BinanceErrorKind::Unauthorized,
+        418 | 429 => BinanceErrorKind::RateLimited,
+        500.. => BinanceErrorKind::Unavailable,
+        _ => BinanceErrorKind::InvalidRequest
+      }
+    };
+
+    // Only an order can be left half done, a read whose status is unknown is just unavailable
+    if kind == BinanceErrorKind::ExecutionUnknown && !is_order { BinanceErrorKind::Unavailable } else { kind }
+  }
+}
+
 pub type IsStore = bool;
 pub type IsTrade = bool;
 
@@ -26,20 +123,88 @@ pub enum Mode {
   Listener(IsStore, IsTrade),
 }
 
+/// Graph Mode
+/// TopOfBook: edge rates from best bid and ask
+/// Vwap: edge rates from cached orderbooks at VWAP_NOTIONAL_USD (top of book where no book is cached)
 #[derive(Debug, PartialEq)]
-pub enum Direction {
-  Forward,
-  Reverse
+#[allow(dead_code)]
+pub enum GraphMode {
+  TopOfBook,
+  Vwap,
 }
 
-impl Direction {
-  pub fn orderbook(&self) -> &'static str {
+/// Binance Environment
+/// Production, the spot testnet or custom urls (i.e. a local mock server), each with its own credentials
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum BinanceEnvironment {
+  Production,
+  Testnet,
+  Custom { rest_url: &'static str, ws_url: &'static str }
+}
+
+impl BinanceEnvironment {
+  pub fn rest_url(&self) -> &'static str {
+    match self {
+      Self::Production => "https://api.binance.com",
+      Self::Testnet => "https://testnet.binance.vision",
+      Self::Custom { rest_url, .. } => rest_url
+    }
+  }
+
+  pub fn ws_url(&self) -> &'static str {
     match self {
-      Self::Forward => "bids",
-      Self::Reverse => "asks"
+      Self::Production => "wss://stream.binance.com:9443",
+      Self::Testnet => "wss://stream.testnet.binance.vision",
+      Self::Custom { ws_url, .. } => ws_url
+    }
+  }
+
+  /// Credential Vars
+  /// Names of the api key and secret variables read from the environment or .env file
+  pub fn credential_vars(&self) -> (&'static str, &'static str) {
+    match self {
+      Self::Production => ("BINANCE_API_KEY", "BINANCE_API_SECRET"),
+      Self::Testnet => ("BINANCE_TESTNET_API_KEY", "BINANCE_TESTNET_API_SECRET"),
+      Self::Custom { .. } => ("BINANCE_CUSTOM_API_KEY", "BINANCE_CUSTOM_API_SECRET")
+    }
+  }
+}
+
+/// Binance Config
+/// Resolved urls and credentials used by every Binance request and stream
+#[derive(Debug, Clone)]
+pub struct BinanceConfig {
+  pub rest_url: String,
+  pub ws_url: String,
+  pub api_key: Option<String>,
+  pub api_secret: Option<String>
+}
+
+impl BinanceConfig {
+  pub fn from_constants() -> Self {
+    Self::from_environment(BINANCE_ENVIRONMENT)
+  }
+
+  pub fn from_environment(environment: BinanceEnvironment) -> Self {
+    dotenv::dotenv().ok();
+    let (key_var, secret_var) = environment.credential_vars();
+    Self {
+      rest_url: environment.rest_url().to_string(),
+      ws_url: environment.ws_url().to_string(),
+      api_key: std::env::var(key_var).ok(),
+      api_secret: std::env::var(secret_var).ok()
     }
   }
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Direction {
+  Forward,
+  Reverse
+}
+
+impl Direction {
   pub fn side(&self) -> &'static str {
     match self {
       Self::Forward => "SELL",
@@ -48,21 +213,67 @@ impl Direction {
   }
 }
 
-#[derive(Debug, Clone)]
+/// Symbol Info
+/// Assets, precision and every exchange filter a market order on the symbol has to pass
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SymbolInfo {
   pub symbol: String,
   pub base_asset: String,
   pub quote_asset: String,
   pub base_asset_precision: u8,
   pub quote_asset_precision: u8,
-  pub min_qty: String,
-  pub max_qty: String,
-  pub min_notional: String,
-  pub max_notional: String,
-  pub step_size: String,
+  pub lot_size: LotSizeFilter,
+  pub market_lot_size: Option<LotSizeFilter>,
+  pub notional: NotionalFilter,
+  pub price_filter: Option<PriceFilter>,
+  pub percent_price_by_side: Option<PercentPriceBySideFilter>,
+  pub max_num_orders: Option<u32>,
+  pub quote_order_qty_market_allowed: bool
 }
 
-#[derive(Debug)]
+/// Lot Size Filter
+/// Base asset quantity rules, LOT_SIZE for every order and MARKET_LOT_SIZE on top for market orders
+/// Zero max_qty or step_size means unrestricted, values are exact decimals as Binance sends them
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LotSizeFilter {
+  pub min_qty: Decimal,
+  pub max_qty: Decimal,
+  pub step_size: Decimal
+}
+
+/// Notional Filter
+/// Quote value bounds of an order, each bound only applies to market orders when its flag is set
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NotionalFilter {
+  pub min_notional: Decimal,
+  pub apply_min_to_market: bool,
+  pub max_notional: Decimal,
+  pub apply_max_to_market: bool
+}
+
+/// Price Filter
+/// Price bounds and tick size, zero max_price or tick_size means unrestricted
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PriceFilter {
+  pub min_price: Decimal,
+  pub max_price: Decimal,
+  pub tick_size: Decimal
+}
+
+/// Percent Price By Side Filter
+/// How far from the average price a fill may be, bid multipliers for SELL and ask multipliers for BUY
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PercentPriceBySideFilter {
+  pub bid_multiplier_up: Decimal,
+  pub bid_multiplier_down: Decimal,
+  pub ask_multiplier_up: Decimal,
+  pub ask_multiplier_down: Decimal
+}
+
+/// Exchange Rate
+/// Top of book for a symbol where from is the base asset and to is the quote asset
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[allow(dead_code)]
 pub struct ExchangeRate {
   pub symbol: String,
   pub from: String,
@@ -73,6 +284,79 @@ pub struct ExchangeRate {
   pub best_ask_size: f64
 }
 
+impl ExchangeRate {
+  pub fn from_book_ticker(
+    symbol_info: &SymbolInfo,
+    best_bid_price: f64,
+    best_bid_size: f64,
+    best_ask_price: f64,
+    best_ask_size: f64
+  ) -> Self {
+    Self {
+      symbol: symbol_info.symbol.clone(),
+      from: symbol_info.base_asset.clone(),
+      to: symbol_info.quote_asset.clone(),
+      best_bid_price,
+      best_ask_price,
+      best_bid_size,
+      best_ask_size
+    }
+  }
+}
+
+/// Order Book
+/// Cached depth for a symbol, bids best first and asks best first
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct OrderBook {
+  pub bids: Vec<(f64, f64)>,
+  pub asks: Vec<(f64, f64)>,
+  pub timestamp: u64
+}
+
+/// Directed Rate
+/// Rate received converting from one asset into another by trading symbol in direction
+/// Forward sells the base asset into bids, Reverse buys the base asset from asks
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DirectedRate {
+  pub from: String,
+  pub to: String,
+  pub rate: f64,
+  pub symbol: String,
+  pub direction: Direction
+}
+
+/// Fee Schedule
+/// Taker fees charged per symbol, used to net fees out of rates
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FeeSchedule {
+  pub default_taker_fee: f64,
+  pub overrides: HashMap<String, f64>,
+  pub is_bnb_discount: bool
+}
+
+impl FeeSchedule {
+  pub fn from_constants() -> Self {
+    Self {
+      default_taker_fee: TAKER_FEE,
+      overrides: TAKER_FEE_OVERRIDES.iter().map(|(symbol, fee)| (symbol.to_string(), *fee)).collect(),
+      is_bnb_discount: IS_BNB_FEE_DISCOUNT
+    }
+  }
+
+  /// Taker Fee
+  /// Fee charged on a market order for the given symbol
+  pub fn taker_fee(&self, symbol: &str) -> f64 {
+    let fee = self.overrides.get(symbol).copied().unwrap_or(self.default_taker_fee);
+    if self.is_bnb_discount { fee * (1.0 - BNB_FEE_DISCOUNT) } else { fee }
+  }
+
+  /// Net Rate
+  /// Rate received after the taker fee has been deducted
+  pub fn net_rate(&self, symbol: &str, rate: f64) -> f64 {
+    rate * (1.0 - self.taker_fee(symbol))
+  }
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct ArbData {
   pub timestamp: u64,
@@ -86,5 +370,7 @@ pub struct ArbData {
   pub asset_4: Option<String>,
   pub asset_5: Option<String>,
   pub asset_6: Option<String>,
-  pub asset_7: Option<String>
+  pub asset_7: Option<String>,
+  pub arb_legs: String,
+  pub cycle_id: String
 }
@@ -1,26 +1,73 @@
-use super::bellmanford::Edge;
-use super::models::{Direction, SmartError, SymbolInfo};
+use super::bellmanford::{BellmanFord, Edge, Route};
+use super::constants::{ASSET_HOLDINGS, MAX_CYCLE_LENGTH, TOP_N_CYCLES};
+use super::models::{DirectedRate, Direction, ExchangeRate, FeeSchedule, SmartError, SymbolInfo};
 
 use async_trait::async_trait;
+use rust_decimal::Decimal;
 use std::collections::HashMap;
 
+#[allow(dead_code)]
 pub trait ExchangeData {
   fn symbols(&self) -> &HashMap<String, SymbolInfo>;
   fn prices(&self) -> &HashMap<String, f64>;
-  fn exchange_rates(&self) -> &Vec<(String, String, f64)>;
+  fn book_tickers(&self) -> &HashMap<String, ExchangeRate>;
+  fn exchange_rates(&self) -> &Vec<DirectedRate>;
+  fn fees(&self) -> &FeeSchedule;
 }
 
 #[async_trait]
+#[allow(dead_code)]
 pub trait ApiCalls {
   async fn new() -> Self;
-  async fn fetch_symbols() -> Result<HashMap<String, SymbolInfo>, SmartError>;
-  async fn fetch_prices() -> Result<HashMap<String, f64>, SmartError>;
+  async fn fetch_symbols(&self) -> Result<HashMap<String, SymbolInfo>, SmartError>;
+  async fn fetch_prices(&self) -> Result<HashMap<String, f64>, SmartError>;
+  async fn fetch_book_tickers(&self, symbols: &HashMap<String, SymbolInfo>) -> Result<HashMap<String, ExchangeRate>, SmartError>;
   async fn get_orderbook_depth(&self, symbol: &str, direction: &Direction) -> Result<Vec<(f64, f64)>, SmartError>;
-  async fn place_market_order(&self, symbol: &str, direction: &Direction, quantity: f64) -> Result<(String, f64, f64), SmartError>;
-  async fn get_asset_account_balance(&self, asset: &str) -> Result<f64, SmartError>;
+  async fn place_market_order(&self, symbol: &str, direction: &Direction, quantity: Decimal) -> Result<(String, Decimal, Decimal), SmartError>;
+  async fn get_asset_account_balance(&self, asset: &str) -> Result<Decimal, SmartError>;
 }
 
-pub trait BellmanFordEx {
-  fn run_bellman_ford_single(&self) -> Option<Vec<Edge>>;
-  fn run_bellman_ford_multi(&self) -> Vec<Vec<Edge>>;
+/// Bellman Ford Ex
+/// Detection over the exchange rates of any ExchangeData, shared by live and offline exchanges
+#[allow(dead_code)]
+pub trait BellmanFordEx: ExchangeData {
+  fn run_bellman_ford_single(&self) -> Option<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates());
+    bf.find_negative_cycle()
+  }
+
+  fn run_bellman_ford_multi(&self) -> Vec<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates());
+    bf.find_all_negative_cycles_by_component()
+  }
+
+  fn run_bellman_ford_bounded(&self) -> Vec<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates()).prune_dead_ends();
+    bf.find_bounded_negative_cycles(&ASSET_HOLDINGS, MAX_CYCLE_LENGTH)
+  }
+
+  fn run_bellman_ford_parallel(&self) -> Vec<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates()).prune_dead_ends();
+    bf.find_all_negative_cycles_parallel(&ASSET_HOLDINGS)
+  }
+
+  fn run_bellman_ford_spfa(&self) -> Option<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates()).prune_dead_ends();
+    bf.find_anchored_negative_cycle(&ASSET_HOLDINGS)
+  }
+
+  fn run_cycle_enumeration(&self) -> Vec<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates()).prune_dead_ends();
+    bf.enumerate_cycles(&ASSET_HOLDINGS, MAX_CYCLE_LENGTH, TOP_N_CYCLES)
+  }
+
+  fn run_min_mean_cycle(&self) -> Option<Vec<Edge>> {
+    let bf = BellmanFord::new(self.exchange_rates());
+    bf.find_min_mean_cycle()
+  }
+
+  fn find_best_route(&self, from: &str, to: &str, max_hops: usize) -> Option<Route> {
+    let bf = BellmanFord::new(self.exchange_rates());
+    bf.find_best_route(from, to, max_hops)
+  }
 }